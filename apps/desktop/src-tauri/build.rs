@@ -1,3 +1,14 @@
 fn main() {
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("macos") {
+        // Shim for mirroring logs to the unified system log, see src/os_log.c
+        cc::Build::new().file("src/os_log.c").compile("os_log");
+        // Shim for sleep and wake notifications, see src/power.c
+        cc::Build::new().file("src/power.c").compile("power");
+        // Shim for frontmost app changes, see src/app_focus.m
+        cc::Build::new()
+            .file("src/app_focus.m")
+            .flag("-fobjc-arc")
+            .compile("app_focus");
+    }
     tauri_build::build()
 }
@@ -0,0 +1,7 @@
+ObjC.import("AppKit")
+
+function run() {
+    const color = $.NSColor.controlAccentColor.colorUsingColorSpace($.NSColorSpace.sRGBColorSpace)
+    const toHex = (component) => Math.round(component * 255).toString(16).padStart(2, "0")
+    return "#" + toHex(color.redComponent) + toHex(color.greenComponent) + toHex(color.blueComponent)
+}
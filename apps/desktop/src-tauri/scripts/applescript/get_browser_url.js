@@ -0,0 +1,12 @@
+// URL of the active tab in the given browser, by bundle id. Prints nothing when
+// it has no windows.
+function run(argv) {
+    const bundleId = argv[0]
+    const browser = Application(bundleId)
+    if (browser.windows.length === 0) return ""
+
+    if (bundleId === "com.apple.Safari") {
+        return browser.windows[0].currentTab().url() || ""
+    }
+    return browser.windows[0].activeTab().url() || ""
+}
@@ -0,0 +1,30 @@
+// Runs a small snippet in the front tab of the given browser to find how far
+// the page is scrolled and which heading is closest above the viewport's top
+// third. Fails when the browser refuses to run JavaScript from Apple Events
+// (Safari's "Allow JavaScript from Apple Events", Chrome's equivalent), which
+// get_page_scroll_context reports as no context.
+const PAGE_SNIPPET = `(() => {
+    const headings = document.querySelectorAll("h1, h2, h3, h4, h5, h6")
+    const line = window.innerHeight / 3
+    let nearest = null
+    for (const heading of headings) {
+        if (heading.getBoundingClientRect().top > line) break
+        nearest = heading
+    }
+    return JSON.stringify({
+        scroll_y: window.scrollY,
+        nearest_heading: nearest ? nearest.textContent.trim().slice(0, 200) : null,
+    })
+})()`
+
+function run(argv) {
+    const bundleId = argv[0]
+    const browser = Application(bundleId)
+    if (browser.windows.length === 0) return ""
+
+    if (bundleId === "com.apple.Safari") {
+        const tab = browser.windows[0].currentTab()
+        return browser.doJavaScript(PAGE_SNIPPET, { in: tab }) || ""
+    }
+    return browser.windows[0].activeTab().execute({ javascript: PAGE_SNIPPET }) || ""
+}
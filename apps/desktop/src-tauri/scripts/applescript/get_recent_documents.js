@@ -0,0 +1,34 @@
+ObjC.import("Foundation")
+
+// Reads an app's recent documents from its shared file list, which macOS keeps
+// as a keyed archive of bookmarks named after the (lowercased) bundle id
+function run(argv) {
+    const bundleId = argv[0].toLowerCase()
+    const dir = $("~/Library/Application Support/com.apple.sharedfilelist/com.apple.LSSharedFileList.ApplicationRecentDocuments").stringByExpandingTildeInPath.js
+
+    let data = $()
+    for (const ext of ["sfl3", "sfl2"]) {
+        data = $.NSData.dataWithContentsOfFile(`${dir}/${bundleId}.${ext}`)
+        if (!data.isNil()) break
+    }
+    if (data.isNil()) return "[]"
+
+    const archive = $.NSKeyedUnarchiver.unarchiveObjectWithData(data)
+    const items = archive.isNil() ? $() : archive.objectForKey("items")
+    if (items.isNil()) return "[]"
+
+    const documents = []
+    for (let i = 0; i < items.count; i++) {
+        const bookmark = items.objectAtIndex(i).objectForKey("Bookmark")
+        if (bookmark.isNil()) continue
+
+        // Resolve without UI and without mounting volumes, so this stays fast
+        const url = $.NSURL.URLByResolvingBookmarkDataOptionsRelativeToURLBookmarkDataIsStaleError(
+            bookmark, (1 << 8) | (1 << 9), $(), null, null
+        )
+        if (url.isNil()) continue
+        documents.push({ name: url.lastPathComponent.js, path: url.path.js })
+    }
+
+    return JSON.stringify(documents)
+}
@@ -0,0 +1,16 @@
+ObjC.import("CoreGraphics")
+
+// Window number of a process's frontmost on-screen window, for screencapture -l.
+// Empty when it has none.
+function run(argv) {
+    const pid = Number(argv[0])
+    // kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements
+    const info = $.CGWindowListCopyWindowInfo((1 << 0) | (1 << 4), 0)
+    const windows = ObjC.deepUnwrap(ObjC.castRefToObject(info)) || []
+
+    // Listed front to back; layer 0 skips menus and overlays
+    const window = windows.find(
+        (w) => w.kCGWindowOwnerPID === pid && w.kCGWindowLayer === 0
+    )
+    return window ? String(window.kCGWindowNumber) : ""
+}
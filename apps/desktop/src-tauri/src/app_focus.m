@@ -0,0 +1,38 @@
+#import <AppKit/AppKit.h>
+
+typedef void (*thoughts_app_focus_callback)(const char *name, const char *bundle_id, int pid);
+
+static id observer = nil;
+
+// Calls back with the current frontmost app, then on the main thread whenever
+// another app comes to the front. NSWorkspace notifications need Objective-C,
+// so Rust registers through this.
+void thoughts_observe_app_focus(thoughts_app_focus_callback callback) {
+    if (observer != nil) {
+        return;
+    }
+    NSRunningApplication *front = [[NSWorkspace sharedWorkspace] frontmostApplication];
+    if (front != nil) {
+        callback(front.localizedName.UTF8String, front.bundleIdentifier.UTF8String,
+                 front.processIdentifier);
+    }
+    NSNotificationCenter *center = [[NSWorkspace sharedWorkspace] notificationCenter];
+    observer = [center addObserverForName:NSWorkspaceDidActivateApplicationNotification
+                                   object:nil
+                                    queue:[NSOperationQueue mainQueue]
+                               usingBlock:^(NSNotification *notification) {
+                                 NSRunningApplication *app =
+                                     notification.userInfo[NSWorkspaceApplicationKey];
+                                 callback(app.localizedName.UTF8String,
+                                          app.bundleIdentifier.UTF8String,
+                                          app.processIdentifier);
+                               }];
+}
+
+void thoughts_stop_observing_app_focus(void) {
+    if (observer == nil) {
+        return;
+    }
+    [[[NSWorkspace sharedWorkspace] notificationCenter] removeObserver:observer];
+    observer = nil;
+}
@@ -0,0 +1,236 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{SampleFormat, SizedSample};
+
+use crate::AppState;
+
+const DEFAULT_SAMPLE_MS: u64 = 250;
+// Long enough to average out a click or a cough, short enough not to stall
+// the caller
+const MAX_SAMPLE_MS: u64 = 2000;
+
+// dBFS floor reported for digital silence instead of negative infinity
+const SILENCE_DBFS: f32 = -120.0;
+
+#[derive(serde::Serialize)]
+pub struct AmbientLevel {
+    rms: f32,
+    dbfs: f32,
+    duration_ms: u64,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    sum_squares: f64,
+    count: u64,
+    peak: f32,
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    acc: Arc<Mutex<Accumulator>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let mut acc = acc.lock().unwrap();
+            for &sample in data {
+                let value: f32 = cpal::Sample::from_sample(sample);
+                acc.sum_squares += f64::from(value) * f64::from(value);
+                acc.count += 1;
+                acc.peak = acc.peak.max(value.abs());
+            }
+        },
+        |e| log_error!("Ambient level stream error: {e}"),
+        None,
+    )
+}
+
+fn permission_error(e: impl std::fmt::Display) -> tauri::Error {
+    tauri::Error::Io(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!(
+            "Couldn't read from the microphone, check microphone access in System Settings: {e}"
+        ),
+    ))
+}
+
+// The mic picked with set_recording_input_device, persisted as its name in the
+// config dir. None means the system default.
+pub struct InputDevicePreference {
+    path: PathBuf,
+    name: Mutex<Option<String>>,
+}
+
+impl InputDevicePreference {
+    pub fn load(path: PathBuf) -> Self {
+        let name = fs::read_to_string(&path)
+            .ok()
+            .map(|content| content.trim().to_owned())
+            .filter(|name| !name.is_empty());
+        InputDevicePreference {
+            path,
+            name: Mutex::new(name),
+        }
+    }
+
+    fn get(&self) -> Option<String> {
+        self.name.lock().unwrap().clone()
+    }
+
+    fn set(&self, name: Option<String>) -> io::Result<()> {
+        let mut current = self.name.lock().unwrap();
+        match &name {
+            Some(name) => fs::write(&self.path, name)?,
+            None => match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            },
+        }
+        *current = name;
+        Ok(())
+    }
+}
+
+fn find_input_device(name: &str) -> Option<cpal::Device> {
+    cpal::default_host()
+        .input_devices()
+        .ok()?
+        .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+}
+
+// The preferred device if it's still connected, otherwise the default
+fn input_device(preferred: Option<&str>) -> Option<cpal::Device> {
+    if let Some(name) = preferred {
+        match find_input_device(name) {
+            Some(device) => return Some(device),
+            None => log_error!("Input device {name:?} not found, using the default"),
+        }
+    }
+    cpal::default_host().default_input_device()
+}
+
+// Samples the input device and measures its loudness. Nothing is stored; the
+// samples are folded into a running sum as they arrive.
+fn sample_level(
+    preferred_device: Option<&str>,
+    duration: Duration,
+) -> Result<AmbientLevel, tauri::Error> {
+    let device = input_device(preferred_device).ok_or_else(|| {
+        tauri::Error::Io(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "No audio input device available",
+        ))
+    })?;
+    let supported = device.default_input_config().map_err(permission_error)?;
+    let config = supported.config();
+
+    let acc = Arc::new(Mutex::new(Accumulator::default()));
+    let stream = match supported.sample_format() {
+        SampleFormat::F32 => build_stream::<f32>(&device, &config, acc.clone()),
+        SampleFormat::I16 => build_stream::<i16>(&device, &config, acc.clone()),
+        SampleFormat::U16 => build_stream::<u16>(&device, &config, acc.clone()),
+        format => {
+            return Err(tauri::Error::Io(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Unsupported sample format: {format}"),
+            )))
+        }
+    }
+    .map_err(permission_error)?;
+
+    stream.play().map_err(permission_error)?;
+    thread::sleep(duration);
+    drop(stream);
+
+    let acc = acc.lock().unwrap();
+    // macOS hands back pure zeros rather than an error when access is denied
+    if acc.count == 0 || acc.peak == 0.0 {
+        return Err(permission_error("no samples received"));
+    }
+
+    let rms = (acc.sum_squares / acc.count as f64).sqrt() as f32;
+    let dbfs = if rms > 0.0 {
+        (20.0 * rms.log10()).max(SILENCE_DBFS)
+    } else {
+        SILENCE_DBFS
+    };
+
+    Ok(AmbientLevel {
+        rms,
+        dbfs,
+        duration_ms: duration.as_millis() as u64,
+    })
+}
+
+#[tauri::command]
+pub async fn get_ambient_level(
+    state: tauri::State<'_, AppState>,
+    duration_ms: Option<u64>,
+) -> Result<AmbientLevel, tauri::Error> {
+    let duration = Duration::from_millis(
+        duration_ms
+            .unwrap_or(DEFAULT_SAMPLE_MS)
+            .clamp(1, MAX_SAMPLE_MS),
+    );
+    let preferred_device = state.input_device.get();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        sample_level(preferred_device.as_deref(), duration)
+    })
+    .await?
+}
+
+#[derive(serde::Serialize)]
+pub struct InputDevice {
+    name: String,
+    is_default: bool,
+    selected: bool,
+}
+
+#[tauri::command]
+pub fn list_input_devices(state: tauri::State<AppState>) -> Result<Vec<InputDevice>, String> {
+    let host = cpal::default_host();
+    let default_name = host
+        .default_input_device()
+        .and_then(|device| device.name().ok());
+    let selected = state.input_device.get();
+
+    let devices = host
+        .input_devices()
+        .map_err(|e| e.to_string())?
+        .filter_map(|device| device.name().ok())
+        .map(|name| InputDevice {
+            is_default: default_name.as_deref() == Some(name.as_str()),
+            selected: selected.as_deref() == Some(name.as_str()),
+            name,
+        })
+        .collect();
+
+    Ok(devices)
+}
+
+// Pass None to go back to the system default
+#[tauri::command]
+pub fn set_recording_input_device(
+    state: tauri::State<AppState>,
+    name: Option<String>,
+) -> Result<(), String> {
+    if let Some(name) = &name {
+        if find_input_device(name).is_none() {
+            return Err(format!("No input device named {name:?}"));
+        }
+    }
+    state.input_device.set(name).map_err(|e| e.to_string())
+}
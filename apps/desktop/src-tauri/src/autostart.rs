@@ -0,0 +1,97 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::write_atomic;
+
+// Launch at login is a LaunchAgent in the user's library, read by launchd at
+// the next login. It isn't loaded when written, since that would start a
+// second instance right away.
+const AGENT_LABEL: &str = "com.abhi.thoughts";
+
+fn agent_path() -> Result<PathBuf, String> {
+    let home_dir = dirs::home_dir().ok_or("Could not determine home directory")?;
+    Ok(home_dir
+        .join("Library/LaunchAgents")
+        .join(format!("{AGENT_LABEL}.plist")))
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn agent_plist(executable: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{AGENT_LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+        escape_xml(executable)
+    )
+}
+
+fn current_executable() -> Result<String, String> {
+    let path = std::env::current_exe().map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+fn write_agent(path: &Path) -> Result<(), String> {
+    let contents = agent_plist(&current_executable()?);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    write_atomic(path, &contents).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+// Whether the agent is on disk. One left pointing at another copy of the app,
+// e.g. after it was moved or updated, is rewritten for this one.
+#[tauri::command]
+pub fn get_launch_at_login() -> Result<bool, String> {
+    if !cfg!(target_os = "macos") {
+        return Ok(false);
+    }
+
+    let path = agent_path()?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.to_string()),
+    };
+    if contents != agent_plist(&current_executable()?) {
+        tracing::info!(path = %path.display(), "rewriting stale launch agent");
+        write_agent(&path)?;
+    }
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn set_launch_at_login(enabled: bool) -> Result<(), String> {
+    if !cfg!(target_os = "macos") {
+        return Err("Launch at login is only available on macOS".to_string());
+    }
+
+    let path = agent_path()?;
+    if enabled {
+        return write_agent(&path);
+    }
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(format!("Failed to remove {}: {e}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
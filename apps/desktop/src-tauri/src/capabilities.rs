@@ -0,0 +1,207 @@
+use std::path::{Path, PathBuf};
+
+use crate::context::automation_granted;
+use crate::AppState;
+
+// Whether a context source is expected to work right now. Only cheap checks
+// are made: permissions that can be queried without prompting, and whether
+// the app a source reads from is installed. A source reported as available
+// can still fail, e.g. when Automation access is denied for its target app.
+#[derive(serde::Serialize)]
+pub struct Capability {
+    source: &'static str,
+    available: bool,
+    // Why it's expected to fail, when it is
+    reason: Option<String>,
+}
+
+impl Capability {
+    fn check(source: &'static str, requirement: Result<(), String>) -> Self {
+        Capability {
+            source,
+            available: requirement.is_ok(),
+            reason: requirement.err(),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn accessibility_granted() -> bool {
+    #[link(name = "ApplicationServices", kind = "framework")]
+    extern "C" {
+        fn AXIsProcessTrusted() -> u8;
+    }
+    // Only reports the current state, never shows the permission prompt
+    unsafe { AXIsProcessTrusted() != 0 }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn accessibility_granted() -> bool {
+    false
+}
+
+// The macOS permissions context sources depend on. Without them most sources
+// fail with nothing to show for it, so the frontend prompts from this.
+#[derive(serde::Serialize)]
+pub struct Permissions {
+    accessibility: bool,
+    automation: bool,
+}
+
+#[tauri::command]
+pub fn check_permissions(state: tauri::State<AppState>) -> Permissions {
+    Permissions {
+        accessibility: accessibility_granted(),
+        automation: cfg!(target_os = "macos") && automation_granted(&state.context_tasks),
+    }
+}
+
+// Opens the System Settings pane for the first permission that's missing
+#[cfg(target_os = "macos")]
+#[tauri::command]
+pub fn request_permissions(state: tauri::State<AppState>) -> Result<(), String> {
+    let pane = if !accessibility_granted() {
+        "Privacy_Accessibility"
+    } else if !automation_granted(&state.context_tasks) {
+        "Privacy_Automation"
+    } else {
+        return Ok(());
+    };
+
+    std::process::Command::new("open")
+        .arg(format!(
+            "x-apple.systempreferences:com.apple.preference.security?{pane}"
+        ))
+        .status()
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+#[tauri::command]
+pub fn request_permissions() -> Result<(), String> {
+    Err("Permissions are only needed on macOS".to_string())
+}
+
+fn app_installed(name: &str) -> bool {
+    let bundle = format!("{name}.app");
+    let user_apps = dirs::home_dir().map(|home| home.join("Applications"));
+    [
+        Some(PathBuf::from("/Applications")),
+        Some(PathBuf::from("/System/Applications")),
+        user_apps,
+    ]
+    .into_iter()
+    .flatten()
+    .any(|dir| dir.join(&bundle).exists())
+}
+
+fn require_app(name: &str) -> Result<(), String> {
+    if app_installed(name) {
+        Ok(())
+    } else {
+        Err(format!("{name} is not installed"))
+    }
+}
+
+fn require_accessibility() -> Result<(), String> {
+    if accessibility_granted() {
+        Ok(())
+    } else {
+        Err("Accessibility access has not been granted".to_string())
+    }
+}
+
+// Checked without running git, since the /usr/bin/git shim offers to install
+// the developer tools when they're missing
+fn require_git() -> Result<(), String> {
+    let installed = ["/opt/homebrew/bin/git", "/usr/local/bin/git"]
+        .iter()
+        .any(|path| Path::new(path).exists())
+        || std::process::Command::new("xcode-select")
+            .arg("-p")
+            .output()
+            .is_ok_and(|output| output.status.success());
+    if installed {
+        Ok(())
+    } else {
+        Err("git is not installed".to_string())
+    }
+}
+
+fn require_input_device() -> Result<(), String> {
+    use cpal::traits::HostTrait;
+
+    if cpal::default_host().default_input_device().is_some() {
+        Ok(())
+    } else {
+        Err("No audio input device available".to_string())
+    }
+}
+
+#[tauri::command]
+pub fn get_capability_matrix(state: tauri::State<AppState>) -> Vec<Capability> {
+    let mut capabilities = vec![
+        Capability::check("git", require_git()),
+        Capability::check("ambient_level", require_input_device()),
+    ];
+
+    if !cfg!(target_os = "macos") {
+        let mac_only = [
+            "focused_app",
+            "window_bounds",
+            "window_title",
+            "arc",
+            "browser_url",
+            "spotify",
+            "apple_music",
+            "ide",
+            "terminal",
+            "mail",
+            "recent_documents",
+            "location",
+            "system_volume",
+            "idle_time",
+        ];
+        capabilities.extend(
+            mac_only.into_iter().map(|source| {
+                Capability::check(source, Err("Only available on macOS".to_string()))
+            }),
+        );
+        return capabilities;
+    }
+
+    let terminal = if state.config.terminal_history_enabled() {
+        Ok(())
+    } else {
+        Err("Terminal history is disabled".to_string())
+    };
+    let mail = if state.config.email_context_enabled() {
+        require_app("Mail")
+    } else {
+        Err("Email context is disabled".to_string())
+    };
+    let location = if Path::new("/opt/homebrew/bin/CoreLocationCLI").exists() {
+        Ok(())
+    } else {
+        Err("CoreLocationCLI is not installed".to_string())
+    };
+
+    capabilities.extend([
+        Capability::check("focused_app", Ok(())),
+        Capability::check("window_bounds", require_accessibility()),
+        Capability::check("window_title", require_accessibility()),
+        Capability::check("arc", require_app("Arc")),
+        Capability::check("browser_url", Ok(())),
+        Capability::check("spotify", require_app("Spotify")),
+        Capability::check("apple_music", require_app("Music")),
+        Capability::check("ide", require_app("Xcode")),
+        Capability::check("terminal", terminal),
+        Capability::check("mail", mail),
+        Capability::check("recent_documents", Ok(())),
+        Capability::check("location", location),
+        Capability::check("system_volume", Ok(())),
+        Capability::check("idle_time", Ok(())),
+    ]);
+    capabilities
+}
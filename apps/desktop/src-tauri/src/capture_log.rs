@@ -0,0 +1,293 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{Map, Value};
+
+use crate::AppState;
+
+// How far from the requested time a snapshot may be and still count as a match
+const DEFAULT_TOLERANCE_MS: u64 = 60_000;
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct CaptureSnapshot {
+    // Milliseconds since the Unix epoch, matching the frontend's Date.now()
+    pub timestamp: u64,
+    pub context: Value,
+}
+
+struct LogLine {
+    start: u64,
+    end: u64,
+    snapshot: CaptureSnapshot,
+}
+
+// Append-only JSONL log of context snapshots taken while record mode is on.
+// Snapshots are written in timestamp order, which lets lookups binary-search
+// the file by byte offset instead of reading it end to end.
+pub struct CaptureLog {
+    path: PathBuf,
+}
+
+impl CaptureLog {
+    pub fn new(path: PathBuf) -> Self {
+        CaptureLog { path }
+    }
+
+    pub fn append(&self, snapshot: &CaptureSnapshot) -> io::Result<()> {
+        let mut line = serde_json::to_string(snapshot)?;
+        line.push('\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())
+    }
+
+    pub fn nearest(
+        &self,
+        timestamp: u64,
+        tolerance_ms: u64,
+    ) -> io::Result<Option<CaptureSnapshot>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        // Find the first snapshot at or after `timestamp`, remembering the last
+        // one before it so both neighbours can be compared afterwards.
+        let mut lo = 0;
+        let mut hi = len;
+        let mut after: Option<CaptureSnapshot> = None;
+        let mut before: Option<CaptureSnapshot> = None;
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match read_line_from(&mut reader, mid)? {
+                Some(line) if line.start < hi => {
+                    if line.snapshot.timestamp < timestamp {
+                        lo = line.end;
+                        before = Some(line.snapshot);
+                    } else {
+                        hi = mid;
+                        after = Some(line.snapshot);
+                    }
+                }
+                _ => hi = mid,
+            }
+        }
+
+        let nearest = [before, after]
+            .into_iter()
+            .flatten()
+            .min_by_key(|snapshot| snapshot.timestamp.abs_diff(timestamp));
+
+        Ok(nearest.filter(|snapshot| snapshot.timestamp.abs_diff(timestamp) <= tolerance_ms))
+    }
+}
+
+// Reads the first well-formed line starting at or after `pos`
+fn read_line_from(reader: &mut BufReader<File>, pos: u64) -> io::Result<Option<LogLine>> {
+    let mut start = pos;
+    if pos > 0 {
+        // Back up one byte so a line starting exactly at `pos` isn't skipped
+        reader.seek(SeekFrom::Start(pos - 1))?;
+        let mut partial = Vec::new();
+        start = pos - 1 + reader.read_until(b'\n', &mut partial)? as u64;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+
+    loop {
+        let mut buf = String::new();
+        let read = reader.read_line(&mut buf)? as u64;
+        if read == 0 {
+            return Ok(None);
+        }
+
+        let end = start + read;
+        if let Ok(snapshot) = serde_json::from_str::<CaptureSnapshot>(buf.trim()) {
+            return Ok(Some(LogLine {
+                start,
+                end,
+                snapshot,
+            }));
+        }
+        start = end;
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+// Metadata stashed by automation for whichever capture happens next. Taking it
+// empties the stash, so it's applied at most once.
+#[derive(Default)]
+pub struct NextCaptureMetadata(Mutex<Option<Map<String, Value>>>);
+
+impl NextCaptureMetadata {
+    fn set(&self, metadata: Map<String, Value>) {
+        *self.0.lock().unwrap() = Some(metadata);
+    }
+
+    fn take(&self) -> Option<Map<String, Value>> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+#[tauri::command]
+pub fn set_next_capture_metadata(state: tauri::State<AppState>, metadata: Map<String, Value>) {
+    state.next_capture_metadata.set(metadata);
+}
+
+// Used by the panel when it saves a thought
+#[tauri::command]
+pub fn take_next_capture_metadata(state: tauri::State<AppState>) -> Option<Map<String, Value>> {
+    state.next_capture_metadata.take()
+}
+
+#[tauri::command]
+pub fn record_context(state: tauri::State<AppState>, mut context: Value) -> Result<bool, String> {
+    if !state.record_mode.lock().unwrap().enabled {
+        return Ok(false);
+    }
+
+    if let Value::Object(fields) = &mut context {
+        if let Some(metadata) = state.next_capture_metadata.take() {
+            fields.extend(metadata);
+        }
+    }
+
+    let snapshot = CaptureSnapshot {
+        timestamp: now_ms(),
+        context,
+    };
+    state
+        .capture_log
+        .append(&snapshot)
+        .map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn get_context_at(
+    state: tauri::State<AppState>,
+    timestamp: u64,
+    tolerance_ms: Option<u64>,
+) -> Result<Option<CaptureSnapshot>, String> {
+    state
+        .capture_log
+        .nearest(timestamp, tolerance_ms.unwrap_or(DEFAULT_TOLERANCE_MS))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn log_with(dir: &TempDir, lines: &[&str]) -> CaptureLog {
+        let path = dir.path().join("captures.jsonl");
+        let mut content = lines.join("\n");
+        content.push('\n');
+        std::fs::write(&path, content).unwrap();
+        CaptureLog::new(path)
+    }
+
+    fn snapshot(timestamp: u64) -> String {
+        serde_json::to_string(&CaptureSnapshot {
+            timestamp,
+            context: Value::Null,
+        })
+        .unwrap()
+    }
+
+    fn nearest(log: &CaptureLog, timestamp: u64, tolerance_ms: u64) -> Option<u64> {
+        log.nearest(timestamp, tolerance_ms)
+            .unwrap()
+            .map(|snapshot| snapshot.timestamp)
+    }
+
+    #[test]
+    fn missing_or_empty_log_has_no_match() {
+        let dir = TempDir::new();
+        let missing = CaptureLog::new(dir.path().join("missing.jsonl"));
+        assert_eq!(nearest(&missing, 1000, DEFAULT_TOLERANCE_MS), None);
+
+        let path = dir.path().join("empty.jsonl");
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(
+            nearest(&CaptureLog::new(path), 1000, DEFAULT_TOLERANCE_MS),
+            None
+        );
+    }
+
+    #[test]
+    fn picks_the_closer_neighbour() {
+        let dir = TempDir::new();
+        let log = log_with(&dir, &[&snapshot(1000), &snapshot(2000), &snapshot(3000)]);
+        assert_eq!(nearest(&log, 2000, 0), Some(2000));
+        assert_eq!(nearest(&log, 2400, 1000), Some(2000));
+        assert_eq!(nearest(&log, 2600, 1000), Some(3000));
+        assert_eq!(nearest(&log, 0, 1000), Some(1000));
+        assert_eq!(nearest(&log, 9000, 6000), Some(3000));
+    }
+
+    #[test]
+    fn tolerance_is_inclusive() {
+        let dir = TempDir::new();
+        let log = log_with(&dir, &[&snapshot(1000), &snapshot(3000)]);
+        assert_eq!(nearest(&log, 3600, 600), Some(3000));
+        assert_eq!(nearest(&log, 3600, 599), None);
+        assert_eq!(nearest(&log, 400, 600), Some(1000));
+        assert_eq!(nearest(&log, 399, 600), None);
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        let dir = TempDir::new();
+        let log = log_with(
+            &dir,
+            &[
+                "not json",
+                &snapshot(1000),
+                "{\"timestamp\": 15",
+                "",
+                &snapshot(2000),
+                "{}",
+                &snapshot(3000),
+                "trailing garbage",
+            ],
+        );
+        assert_eq!(nearest(&log, 1000, 0), Some(1000));
+        assert_eq!(nearest(&log, 2100, 500), Some(2000));
+        assert_eq!(nearest(&log, 3000, 0), Some(3000));
+        assert_eq!(nearest(&log, 1500, 100), None);
+    }
+
+    #[test]
+    fn appended_snapshots_are_found() {
+        let dir = TempDir::new();
+        let log = CaptureLog::new(dir.path().join("captures.jsonl"));
+        for timestamp in (0..50).map(|i| 1000 + i * 100) {
+            log.append(&CaptureSnapshot {
+                timestamp,
+                context: Value::Null,
+            })
+            .unwrap();
+        }
+        for timestamp in (0..50).map(|i| 1000 + i * 100) {
+            assert_eq!(nearest(&log, timestamp + 10, 10), Some(timestamp));
+        }
+    }
+}
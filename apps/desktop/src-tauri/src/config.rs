@@ -1,46 +1,618 @@
+use std::collections::HashMap;
 use std::io;
-use std::path::PathBuf;
-use std::{env, fs};
+use std::net::{Ipv4Addr, Ipv6Addr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::{env, fs, thread};
+
+use tracing::level_filters::LevelFilter;
+
+use crate::server::{kill_process, process_alive, reconcile_pid_files, terminate_process};
+
+// Applies to any context source without its own entry in `source_timeouts`
+const DEFAULT_CONTEXT_TIMEOUT_MS: u64 = 5000;
+
+// How long a context command's result is reused before its script runs again
+const DEFAULT_CONTEXT_CACHE_TTL_MS: u64 = 2000;
+
+// A location read within this long is reused instead of asking CoreLocation
+const DEFAULT_LOCATION_MAX_AGE_SECS: u64 = 300;
+
+// Clipboard text past this many characters is cut off before it's returned
+const DEFAULT_CLIPBOARD_MAX_CHARS: usize = 2000;
+
+// How many ports above the preferred one are tried when it's taken
+const PORT_SEARCH_RANGE: u16 = 20;
+
+// Presses of the same shortcut closer together than this count as one
+const DEFAULT_SHORTCUT_DEBOUNCE_MS: u64 = 120;
+
+// How long the server gets to flush in-flight writes after SIGTERM before it's
+// killed
+const DEFAULT_SHUTDOWN_TIMEOUT_MS: u64 = 3000;
+
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const DEFAULT_PORT: u16 = 4318;
+
+const CONFIG_FILE: &str = "config.toml";
+
+// Layout of the config dir this build expects, recorded in VERSION_FILE. Bump
+// it with a step in `migrate` whenever files move or change shape.
+const CONFIG_SCHEMA_VERSION: u32 = 1;
+
+const VERSION_FILE: &str = "version";
+
+// The panel shortcut picked in the app, see try_register_shortcut
+const SHORTCUT_FILE: &str = "shortcut";
+
+// Transient errors get this many more tries before a PID file write gives up
+const PID_WRITE_RETRIES: u32 = 2;
+
+const PID_WRITE_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+// Settings read from config.toml in the config dir. Everything is optional;
+// env vars take precedence over the file, and the file over the defaults.
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    port: Option<u16>,
+    shortcut: Option<String>,
+    main_window_shortcut: Option<String>,
+    record_mode_shortcut: Option<String>,
+    start_hidden: Option<bool>,
+    hide_on_blur: Option<bool>,
+    show_in_dock: Option<bool>,
+    suppress_in_apps: Option<Vec<String>>,
+    suppress_in_fullscreen: Option<bool>,
+}
+
+impl FileConfig {
+    fn load(config_dir: &Path) -> io::Result<Self> {
+        let path = config_dir.join(CONFIG_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(FileConfig::default()),
+            Err(e) => {
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!("Failed to read {}: {e}", path.display()),
+                ))
+            }
+        };
+        toml::from_str(&content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid config file {}: {e}", path.display()),
+            )
+        })
+    }
+}
+
+// Chrome for the main window. Only takes effect on macOS, and only for windows
+// created after it changes; an open window keeps its style until recreated.
+#[derive(Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleBarStyle {
+    Visible,
+    #[default]
+    Overlay,
+    Transparent,
+}
+
+impl TitleBarStyle {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "visible" => Some(TitleBarStyle::Visible),
+            "overlay" => Some(TitleBarStyle::Overlay),
+            "transparent" => Some(TitleBarStyle::Transparent),
+            _ => None,
+        }
+    }
+}
+
+// Which display the main window opens on. Falls back to the primary display
+// when the chosen one isn't connected.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum MainWindowDisplay {
+    #[default]
+    Primary,
+    Index(usize),
+    Cursor,
+    ActiveWindow,
+}
+
+impl MainWindowDisplay {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "primary" => Some(MainWindowDisplay::Primary),
+            "cursor" => Some(MainWindowDisplay::Cursor),
+            "active-window" => Some(MainWindowDisplay::ActiveWindow),
+            other => other.parse().ok().map(MainWindowDisplay::Index),
+        }
+    }
+}
+
+// Where the main window was last left, in logical pixels
+#[derive(Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct WindowGeometry {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
 
 pub struct Config {
     config_dir: PathBuf,
+    // Whether config_dir is the fallback under the temp dir
+    temporary: bool,
+    profile: Option<String>,
     port: u16,
+    shortcut: Option<String>,
+    main_window_shortcut: Option<String>,
+    record_mode_shortcut: Option<String>,
+    start_hidden: bool,
+    hide_on_blur: bool,
+    show_in_dock: bool,
+    suppress_in_apps: Vec<String>,
+    suppress_in_fullscreen: bool,
+    privacy_blur_shortcut: Option<String>,
+    privacy_blur_on_focus_loss: bool,
+    update_manifest_url: Option<String>,
+    context_timeout_ms: u64,
+    context_cache_ttl_ms: u64,
+    location_max_age_secs: u64,
+    clipboard_max_chars: usize,
+    source_timeouts: HashMap<String, u64>,
+    terminal_history_enabled: bool,
+    email_context_enabled: bool,
+    main_window_title_bar_style: TitleBarStyle,
+    os_log_enabled: bool,
+    main_window_display: MainWindowDisplay,
+    metrics_port: Option<u16>,
+    panel_auto_resize: bool,
+    trace_level: LevelFilter,
+    shortcut_debounce_ms: u64,
+    shutdown_timeout_ms: u64,
 }
 
 impl Config {
-    pub fn new(port: u16) -> Result<Self, io::Error> {
-        let config_dir = match env::var("THOUGHTS_CONFIG_PATH").map(PathBuf::from) {
+    // A profile gets its own dir under the config dir, and with it its own
+    // config.toml, PID file, control socket and server data
+    pub fn new(profile: Option<&str>) -> Result<Self, io::Error> {
+        if let Some(name) = profile {
+            check_profile_name(name)?;
+        }
+
+        let base_dir = match env::var("THOUGHTS_CONFIG_PATH").map(PathBuf::from) {
             Ok(path) => path,
             Err(_) => {
-                let home_dir = dirs::home_dir().ok_or_else(|| {
-                    io::Error::new(
-                        io::ErrorKind::NotFound,
-                        "Could not determine home directory",
-                    )
-                })?;
-                eprintln!(
-                    "Warning: THOUGHTS_CONFIG_PATH not set, using home directory as fallback"
-                );
-                home_dir.join(".thoughts")
+                let default_dir = default_config_dir()?;
+                match read_relocation_pointer(&default_dir) {
+                    Some(path) => path,
+                    None => {
+                        eprintln!(
+                            "Warning: THOUGHTS_CONFIG_PATH not set, using home directory as fallback"
+                        );
+                        default_dir
+                    }
+                }
             }
         };
 
-        // Ensure config directory exists
-        fs::create_dir_all(&config_dir)?;
+        let profile_dir = |base: PathBuf| match profile {
+            Some(name) => base.join(name),
+            None => base,
+        };
+        let (config_dir, temporary) = writable_config_dir(
+            profile_dir(base_dir),
+            profile_dir(env::temp_dir().join("thoughts")),
+        )?;
+        check_schema_version(&config_dir)?;
+
+        let file = FileConfig::load(&config_dir)?;
+
+        // Only the preferred port until pick_port runs, see there
+        let port = env::var("SIDECAR_PORT")
+            .ok()
+            .and_then(|p| p.parse().ok())
+            .or(file.port)
+            .unwrap_or(DEFAULT_PORT);
+
+        // Replaces the default panel shortcut, e.g. "Ctrl+Shift+Space". One
+        // picked in the app wins over config.toml.
+        let picked_shortcut = fs::read_to_string(config_dir.join(SHORTCUT_FILE))
+            .ok()
+            .map(|value| value.trim().to_owned())
+            .filter(|value| !value.is_empty());
+        let shortcut = env::var("THOUGHTS_SHORTCUT")
+            .ok()
+            .or(picked_shortcut)
+            .or(file.shortcut);
+        // Replaces the default main window shortcut, Alt+Shift+T
+        let main_window_shortcut = env::var("THOUGHTS_MAIN_WINDOW_SHORTCUT")
+            .ok()
+            .or(file.main_window_shortcut);
+        // Replaces the default record mode shortcut, Alt+Shift+R
+        let record_mode_shortcut = env::var("THOUGHTS_RECORD_MODE_SHORTCUT")
+            .ok()
+            .or(file.record_mode_shortcut);
+
+        // The panel waits for the shortcut unless this is turned off
+        let start_hidden = env_bool("THOUGHTS_START_HIDDEN")
+            .or(file.start_hidden)
+            .unwrap_or(true);
+        // Hiding the panel on focus loss gets in the way of the dev tools, so
+        // it's only on by default in release builds
+        let hide_on_blur = env_bool("THOUGHTS_HIDE_ON_BLUR")
+            .or(file.hide_on_blur)
+            .unwrap_or(!cfg!(debug_assertions));
+        // The app lives in the menu bar unless this puts it in the Dock too
+        let show_in_dock = env_bool("THOUGHTS_SHOW_IN_DOCK")
+            .or(file.show_in_dock)
+            .unwrap_or(false);
+        // Bundle ids of apps the panel stays out of, e.g.
+        // THOUGHTS_SUPPRESS_IN_APPS="us.zoom.xos,com.apple.Keynote"
+        let suppress_in_apps = env::var("THOUGHTS_SUPPRESS_IN_APPS")
+            .ok()
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|id| id.trim().to_owned())
+                    .filter(|id| !id.is_empty())
+                    .collect()
+            })
+            .or(file.suppress_in_apps)
+            .unwrap_or_default();
+        let suppress_in_fullscreen = env_bool("THOUGHTS_SUPPRESS_IN_FULLSCREEN")
+            .or(file.suppress_in_fullscreen)
+            .unwrap_or(false);
+        let privacy_blur_shortcut = env::var("THOUGHTS_PRIVACY_BLUR_SHORTCUT").ok();
+        let privacy_blur_on_focus_loss = env_flag("THOUGHTS_PRIVACY_BLUR_ON_FOCUS_LOSS");
+        // Where check_for_update reads the latest version from
+        let update_manifest_url = env::var("THOUGHTS_UPDATE_MANIFEST_URL").ok();
+
+        let context_timeout_ms = env::var("THOUGHTS_CONTEXT_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CONTEXT_TIMEOUT_MS);
+        // Set to 0 to read every source fresh on each call
+        let context_cache_ttl_ms = env::var("THOUGHTS_CONTEXT_CACHE_TTL_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CONTEXT_CACHE_TTL_MS);
+        let location_max_age_secs = env::var("THOUGHTS_LOCATION_MAX_AGE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_LOCATION_MAX_AGE_SECS);
+        let clipboard_max_chars = env::var("THOUGHTS_CLIPBOARD_MAX_CHARS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CLIPBOARD_MAX_CHARS);
+        // e.g. THOUGHTS_SOURCE_TIMEOUTS="location=8000,spotify=1500"
+        let source_timeouts = env::var("THOUGHTS_SOURCE_TIMEOUTS")
+            .map(|v| parse_source_timeouts(&v))
+            .unwrap_or_default();
 
-        Ok(Config { config_dir, port })
+        let terminal_history_enabled = !env_flag("THOUGHTS_DISABLE_TERMINAL_HISTORY");
+        let email_context_enabled = !env_flag("THOUGHTS_DISABLE_EMAIL_CONTEXT");
+
+        let main_window_title_bar_style = match env::var("THOUGHTS_MAIN_WINDOW_TITLE_BAR_STYLE") {
+            Ok(value) => TitleBarStyle::parse(&value).unwrap_or_else(|| {
+                eprintln!("Unknown title bar style {value:?}, using overlay");
+                TitleBarStyle::default()
+            }),
+            Err(_) => TitleBarStyle::default(),
+        };
+
+        // A display index, "cursor", "active-window" or "primary"
+        let main_window_display = match env::var("THOUGHTS_MAIN_WINDOW_DISPLAY") {
+            Ok(value) => MainWindowDisplay::parse(&value).unwrap_or_else(|| {
+                eprintln!("Unknown main window display {value:?}, using primary");
+                MainWindowDisplay::default()
+            }),
+            Err(_) => MainWindowDisplay::default(),
+        };
+
+        // Fits the panel window to its content instead of a fixed size
+        let panel_auto_resize = env_flag("THOUGHTS_PANEL_AUTO_RESIZE");
+
+        // Starting verbosity of the trace log, adjustable with set_trace_level
+        let trace_level = match env::var("THOUGHTS_TRACE_LEVEL") {
+            Ok(value) => value.parse().unwrap_or_else(|_| {
+                eprintln!("Unknown trace level {value:?}, using warn");
+                LevelFilter::WARN
+            }),
+            Err(_) => LevelFilter::WARN,
+        };
+
+        // Set to 0 to let every press through
+        let shortcut_debounce_ms = env::var("THOUGHTS_SHORTCUT_DEBOUNCE_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SHORTCUT_DEBOUNCE_MS);
+
+        let shutdown_timeout_ms = env::var("THOUGHTS_SHUTDOWN_TIMEOUT_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_MS);
+
+        // Exposes /metrics on this localhost port when set
+        let metrics_port = env::var("THOUGHTS_METRICS_PORT")
+            .ok()
+            .and_then(|v| v.parse().ok());
+
+        // Mirrors logs to the unified system log on macOS; off by default to
+        // keep Console.app quiet
+        let os_log_enabled = env_flag("THOUGHTS_OS_LOG");
+
+        Ok(Config {
+            config_dir,
+            temporary,
+            profile: profile.map(str::to_owned),
+            port,
+            shortcut,
+            main_window_shortcut,
+            record_mode_shortcut,
+            start_hidden,
+            hide_on_blur,
+            show_in_dock,
+            suppress_in_apps,
+            suppress_in_fullscreen,
+            privacy_blur_shortcut,
+            privacy_blur_on_focus_loss,
+            update_manifest_url,
+            context_timeout_ms,
+            context_cache_ttl_ms,
+            location_max_age_secs,
+            clipboard_max_chars,
+            source_timeouts,
+            terminal_history_enabled,
+            email_context_enabled,
+            main_window_title_bar_style,
+            os_log_enabled,
+            main_window_display,
+            metrics_port,
+            panel_auto_resize,
+            trace_level,
+            shortcut_debounce_ms,
+            shutdown_timeout_ms,
+        })
+    }
+
+    pub fn get_config_dir(&self) -> &Path {
+        &self.config_dir
     }
 
+    // True when the configured dir wasn't writable and a temporary one is in
+    // use instead, see Config::new
+    pub fn is_temporary(&self) -> bool {
+        self.temporary
+    }
+
+    // Where the server keeps its data. That's the config dir itself, so data
+    // follows the profile and a relocated or temporary config dir.
+    pub fn get_data_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    pub fn get_profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    // The port picked for the server, which may be above the preferred one if
+    // that was taken
     pub fn get_port(&self) -> u16 {
         self.port
     }
 
+    // Moves the server to the first free port from the preferred one up. Runs
+    // after reconcile_pid_files has stopped orphaned servers, which would
+    // otherwise push this instance off its own port, and right before the
+    // server is spawned to keep the window for another process to take it
+    // short.
+    pub fn pick_port(&mut self) -> io::Result<u16> {
+        let preferred = self.port;
+        self.port = find_free_port(preferred)?;
+        if self.port != preferred {
+            tracing::warn!(preferred, port = self.port, "preferred port is taken");
+        }
+        Ok(self.port)
+    }
+
+    pub fn get_shortcut(&self) -> Option<&str> {
+        self.shortcut.as_deref()
+    }
+
+    pub fn get_main_window_shortcut(&self) -> Option<&str> {
+        self.main_window_shortcut.as_deref()
+    }
+
+    pub fn get_record_mode_shortcut(&self) -> Option<&str> {
+        self.record_mode_shortcut.as_deref()
+    }
+
+    pub fn start_hidden(&self) -> bool {
+        self.start_hidden
+    }
+
+    pub fn hide_on_blur(&self) -> bool {
+        self.hide_on_blur
+    }
+
+    pub fn show_in_dock(&self) -> bool {
+        self.show_in_dock
+    }
+
+    pub fn get_suppress_in_apps(&self) -> &[String] {
+        &self.suppress_in_apps
+    }
+
+    pub fn suppress_in_fullscreen(&self) -> bool {
+        self.suppress_in_fullscreen
+    }
+
+    pub fn get_privacy_blur_shortcut(&self) -> Option<&str> {
+        self.privacy_blur_shortcut.as_deref()
+    }
+
+    pub fn get_update_manifest_url(&self) -> Option<&str> {
+        self.update_manifest_url.as_deref()
+    }
+
+    pub fn privacy_blur_on_focus_loss(&self) -> bool {
+        self.privacy_blur_on_focus_loss
+    }
+
+    pub fn get_context_timeout_ms(&self) -> u64 {
+        self.context_timeout_ms
+    }
+
+    pub fn get_context_cache_ttl_ms(&self) -> u64 {
+        self.context_cache_ttl_ms
+    }
+
+    pub fn get_location_max_age_secs(&self) -> u64 {
+        self.location_max_age_secs
+    }
+
+    pub fn get_clipboard_max_chars(&self) -> usize {
+        self.clipboard_max_chars
+    }
+
+    pub fn get_source_timeouts(&self) -> &HashMap<String, u64> {
+        &self.source_timeouts
+    }
+
+    pub fn terminal_history_enabled(&self) -> bool {
+        self.terminal_history_enabled
+    }
+
+    pub fn email_context_enabled(&self) -> bool {
+        self.email_context_enabled
+    }
+
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    pub fn get_main_window_title_bar_style(&self) -> TitleBarStyle {
+        self.main_window_title_bar_style
+    }
+
+    pub fn get_main_window_display(&self) -> MainWindowDisplay {
+        self.main_window_display
+    }
+
+    pub fn panel_auto_resize(&self) -> bool {
+        self.panel_auto_resize
+    }
+
+    pub fn get_trace_level(&self) -> LevelFilter {
+        self.trace_level
+    }
+
+    pub fn get_shortcut_debounce_ms(&self) -> u64 {
+        self.shortcut_debounce_ms
+    }
+
+    pub fn get_shutdown_timeout_ms(&self) -> u64 {
+        self.shutdown_timeout_ms
+    }
+
+    pub fn get_metrics_port(&self) -> Option<u16> {
+        self.metrics_port
+    }
+
+    pub fn os_log_enabled(&self) -> bool {
+        self.os_log_enabled
+    }
+
     pub fn get_pid_file_path(&self) -> PathBuf {
         self.config_dir.join(format!("server-{}.pid", self.port))
     }
 
+    pub fn get_capture_log_path(&self) -> PathBuf {
+        self.config_dir.join("captures.jsonl")
+    }
+
+    pub fn get_servers_dir(&self) -> PathBuf {
+        self.config_dir.join("servers")
+    }
+
+    pub fn get_screenshots_dir(&self) -> PathBuf {
+        self.config_dir.join("screenshots")
+    }
+
+    pub fn get_state_snapshot_path(&self) -> PathBuf {
+        self.config_dir.join("state.json")
+    }
+
+    pub fn get_install_id_path(&self) -> PathBuf {
+        self.config_dir.join("install-id")
+    }
+
+    pub fn get_schedules_path(&self) -> PathBuf {
+        self.config_dir.join("schedules.json")
+    }
+
+    pub fn get_context_sources_path(&self) -> PathBuf {
+        self.config_dir.join("context-sources.json")
+    }
+
+    pub fn get_trace_log_path(&self) -> PathBuf {
+        self.config_dir.join("logs").join("trace.log")
+    }
+
+    pub fn get_shortcut_path(&self) -> PathBuf {
+        self.config_dir.join(SHORTCUT_FILE)
+    }
+
+    pub fn get_input_device_path(&self) -> PathBuf {
+        self.config_dir.join("input-device")
+    }
+
+    pub fn get_streak_path(&self) -> PathBuf {
+        self.config_dir.join("streak.json")
+    }
+
+    pub fn get_window_geometry_path(&self) -> PathBuf {
+        self.config_dir.join("main-window.json")
+    }
+
+    pub fn read_window_geometry(&self) -> Option<WindowGeometry> {
+        let content = fs::read_to_string(self.get_window_geometry_path()).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    pub fn write_window_geometry(&self, geometry: &WindowGeometry) -> io::Result<()> {
+        write_atomic(
+            &self.get_window_geometry_path(),
+            &serde_json::to_string(geometry)?,
+        )
+    }
+
+    pub fn get_control_socket_path(&self) -> PathBuf {
+        self.config_dir.join("control.sock")
+    }
+
+    // Random per-install identifier, generated on first use. Deliberately not
+    // derived from hardware so it carries no information about the machine.
+    pub fn read_or_create_install_id(&self) -> io::Result<String> {
+        let path = self.get_install_id_path();
+        if let Ok(content) = fs::read_to_string(&path) {
+            let id = content.trim();
+            if uuid::Uuid::parse_str(id).is_ok() {
+                return Ok(id.to_owned());
+            }
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        write_atomic(&path, &id)?;
+        Ok(id)
+    }
+
     pub fn write_pid_file(&self, pid: u32) -> io::Result<()> {
-        fs::write(self.get_pid_file_path(), pid.to_string())
+        retry_transient(PID_WRITE_RETRIES, || {
+            fs::write(self.get_pid_file_path(), pid.to_string())
+        })
     }
 
     pub fn read_pid_file(&self) -> Option<u32> {
@@ -49,18 +621,543 @@ impl Config {
             .and_then(|content| content.trim().parse().ok())
     }
 
-    pub fn cleanup_existing_server(&self) {
-        if let Some(pid) = self.read_pid_file() {
-            // Try to kill the process
-            unsafe {
-                libc::kill(pid as i32, libc::SIGTERM);
+    pub fn cleanup_pid_file(&self) {
+        let _ = fs::remove_file(self.get_pid_file_path());
+    }
+
+    // Sends SIGTERM and waits up to `timeout` for the server to exit, then
+    // sends SIGKILL. The PID file is only removed once the process is gone,
+    // so a server that outlives this is still found on the next launch.
+    // Returns false when the server had to be killed.
+    pub fn terminate_server_gracefully(&self, pid: u32, timeout: Duration) -> bool {
+        terminate_process(pid);
+        let deadline = Instant::now() + timeout;
+        while process_alive(pid) && Instant::now() < deadline {
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
+        }
+
+        let graceful = !process_alive(pid);
+        if !graceful {
+            tracing::warn!(pid, "server ignored SIGTERM, killing it");
+            kill_process(pid);
+            let deadline = Instant::now() + timeout;
+            while process_alive(pid) && Instant::now() < deadline {
+                thread::sleep(SHUTDOWN_POLL_INTERVAL);
             }
-            // Remove the PID file regardless of kill success
-            let _ = fs::remove_file(self.get_pid_file_path());
         }
+
+        if !process_alive(pid) && self.read_pid_file() == Some(pid) {
+            self.cleanup_pid_file();
+        }
+        graceful
     }
 
-    pub fn cleanup_pid_file(&self) {
-        let _ = fs::remove_file(self.get_pid_file_path());
+    // Stops servers left behind under any port, not just the current one, so
+    // PID files don't pile up when the port changes. Runs once at startup,
+    // before this instance spawns its own server. See reconcile_pid_files.
+    pub fn cleanup_all_servers(&self) -> Vec<String> {
+        reconcile_pid_files(self, None)
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    // Defaults for everything, without reading the environment, so tests can
+    // run in parallel against their own dir
+    pub fn for_test(config_dir: &Path, port: u16) -> Self {
+        Config {
+            config_dir: config_dir.to_owned(),
+            temporary: false,
+            profile: None,
+            port,
+            shortcut: None,
+            main_window_shortcut: None,
+            record_mode_shortcut: None,
+            start_hidden: true,
+            hide_on_blur: false,
+            show_in_dock: false,
+            suppress_in_apps: Vec::new(),
+            suppress_in_fullscreen: false,
+            privacy_blur_shortcut: None,
+            privacy_blur_on_focus_loss: false,
+            update_manifest_url: None,
+            context_timeout_ms: DEFAULT_CONTEXT_TIMEOUT_MS,
+            context_cache_ttl_ms: DEFAULT_CONTEXT_CACHE_TTL_MS,
+            location_max_age_secs: DEFAULT_LOCATION_MAX_AGE_SECS,
+            clipboard_max_chars: DEFAULT_CLIPBOARD_MAX_CHARS,
+            source_timeouts: HashMap::new(),
+            terminal_history_enabled: true,
+            email_context_enabled: true,
+            main_window_title_bar_style: TitleBarStyle::default(),
+            os_log_enabled: false,
+            main_window_display: MainWindowDisplay::default(),
+            metrics_port: None,
+            panel_auto_resize: false,
+            trace_level: LevelFilter::WARN,
+            shortcut_debounce_ms: DEFAULT_SHORTCUT_DEBOUNCE_MS,
+            shutdown_timeout_ms: DEFAULT_SHUTDOWN_TIMEOUT_MS,
+        }
+    }
+}
+
+// Runs `op` again up to `retries` times while it fails with an error that's
+// likely to go away, like an interrupted write
+fn retry_transient<T>(mut retries: u32, mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    loop {
+        match op() {
+            Err(e)
+                if retries > 0
+                    && matches!(
+                        e.kind(),
+                        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+                    ) =>
+            {
+                retries -= 1;
+                thread::sleep(PID_WRITE_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+// `config_dir`, or `fallback` when that isn't writable, along with whether the
+// fallback was used. A config dir on a read-only or full volume would
+// otherwise stop the app from starting. Nothing written to the fallback
+// survives a reboot, so this is loud about it.
+fn writable_config_dir(config_dir: PathBuf, fallback: PathBuf) -> io::Result<(PathBuf, bool)> {
+    match ensure_writable(&config_dir) {
+        Ok(()) => Ok((config_dir, false)),
+        Err(e) => {
+            eprintln!(
+                "WARNING: config dir {} isn't writable ({e}). Using {} for this run; settings, logs and server tracking won't persist.",
+                config_dir.display(),
+                fallback.display()
+            );
+            ensure_writable(&fallback)?;
+            Ok((fallback, true))
+        }
+    }
+}
+
+// Creates the dir if needed and checks a file can actually be written there,
+// which an existing dir on a read-only or full volume would fail
+fn ensure_writable(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = dir.join(".write-test");
+    fs::write(&probe, b"")?;
+    fs::remove_file(&probe)
+}
+
+// Brings a config dir written by an older build up to CONFIG_SCHEMA_VERSION,
+// and refuses one written by a newer build rather than guess at its layout.
+// A dir without a version file predates the marker and counts as version 0.
+fn check_schema_version(config_dir: &Path) -> io::Result<()> {
+    let path = config_dir.join(VERSION_FILE);
+    let version = match fs::read_to_string(&path) {
+        Ok(content) => content.trim().parse::<u32>().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unreadable version marker {}: {content:?}", path.display()),
+            )
+        })?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(e),
+    };
+
+    if version > CONFIG_SCHEMA_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} was set up by a newer version of Thoughts (layout {version}, this build knows up to {CONFIG_SCHEMA_VERSION}). Update the app or point THOUGHTS_CONFIG_PATH elsewhere.",
+                config_dir.display()
+            ),
+        ));
+    }
+    if version < CONFIG_SCHEMA_VERSION {
+        migrate(config_dir, version)?;
+        write_atomic(&path, &CONFIG_SCHEMA_VERSION.to_string())?;
+    }
+    Ok(())
+}
+
+// One step per schema version, each run in order from the dir's version
+fn migrate(config_dir: &Path, from: u32) -> io::Result<()> {
+    if from < 1 {
+        // Version 1 only introduces the marker. The directories are created
+        // on demand elsewhere too, but having them up front means a
+        // read-only location fails here instead of halfway through startup.
+        fs::create_dir_all(config_dir.join("logs"))?;
+        fs::create_dir_all(config_dir.join("servers"))?;
+    }
+    eprintln!(
+        "Migrated {} from layout {from} to {CONFIG_SCHEMA_VERSION}",
+        config_dir.display()
+    );
+    Ok(())
+}
+
+// Profile names become a directory, so they're kept to a single plain
+// path component
+fn check_profile_name(name: &str) -> io::Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid profile name {name:?}, use letters, digits, - and _"),
+        ))
+    }
+}
+
+// `--profile <name>` or `--profile=<name>`
+pub fn profile_from_args(mut args: impl Iterator<Item = String>) -> Option<String> {
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            return args.next();
+        }
+        if let Some(name) = arg.strip_prefix("--profile=") {
+            return Some(name.to_owned());
+        }
+    }
+    None
+}
+
+pub fn default_config_dir() -> io::Result<PathBuf> {
+    let home_dir = dirs::home_dir().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not determine home directory",
+        )
+    })?;
+    Ok(home_dir.join(".thoughts"))
+}
+
+// Left in the default config dir by migrate_config_dir, naming the directory
+// the config moved to
+pub const RELOCATION_POINTER_FILE: &str = "config-dir";
+
+fn read_relocation_pointer(default_dir: &Path) -> Option<PathBuf> {
+    let content = fs::read_to_string(default_dir.join(RELOCATION_POINTER_FILE)).ok()?;
+    let path = PathBuf::from(content.trim());
+    if path.is_dir() {
+        Some(path)
+    } else {
+        eprintln!(
+            "Config dir {} no longer exists, using the default",
+            path.display()
+        );
+        None
+    }
+}
+
+// The first port from `preferred` up that can be bound, so the server doesn't
+// fail to start on one another process holds. The probe is released right
+// away for the server to take.
+fn find_free_port(preferred: u16) -> io::Result<u16> {
+    let last = preferred.saturating_add(PORT_SEARCH_RANGE);
+    (preferred..=last)
+        .find(|&port| port_available(port))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("No free port for the server between {preferred} and {last}"),
+            )
+        })
+}
+
+// The server listens on every interface, Node's default of `::` (or 0.0.0.0
+// without IPv6), so that's where the port has to be free. A probe on
+// 127.0.0.1 alone passes for a port another process holds on 0.0.0.0.
+fn port_available(port: u16) -> bool {
+    match TcpListener::bind((Ipv6Addr::UNSPECIFIED, port)) {
+        Ok(_) => true,
+        Err(e) if e.kind() == io::ErrorKind::AddrNotAvailable => {
+            TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).is_ok()
+        }
+        Err(_) => false,
+    }
+}
+
+fn env_flag(name: &str) -> bool {
+    env::var(name)
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+// Like env_flag, but tells an unset variable apart from one set to false so it
+// can fall back to the config file
+fn env_bool(name: &str) -> Option<bool> {
+    let value = env::var(name).ok()?;
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" => Some(true),
+        "0" | "false" => Some(false),
+        _ => {
+            eprintln!("Ignoring {name}={value:?}, expected true or false");
+            None
+        }
+    }
+}
+
+fn parse_source_timeouts(value: &str) -> HashMap<String, u64> {
+    value
+        .split(',')
+        .filter_map(|entry| {
+            let (source, ms) = entry.split_once('=')?;
+            match ms.trim().parse() {
+                Ok(ms) => Some((source.trim().to_owned(), ms)),
+                Err(_) => {
+                    eprintln!("Ignoring invalid timeout for context source {source:?}: {ms:?}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+// Writes to a sibling temp file and renames it into place, so readers never
+// observe a partially written file
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{unused_port, TempDir};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn args(values: &[&str]) -> impl Iterator<Item = String> {
+        values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn reads_profile_from_args() {
+        assert_eq!(
+            profile_from_args(args(&["--profile", "work"])),
+            Some("work".into())
+        );
+        assert_eq!(
+            profile_from_args(args(&["--headless", "--profile=home"])),
+            Some("home".into())
+        );
+        assert_eq!(profile_from_args(args(&["--profile"])), None);
+        assert_eq!(profile_from_args(args(&["toggle"])), None);
+    }
+
+    #[test]
+    fn rejects_profile_names_that_are_not_one_component() {
+        assert!(check_profile_name("work-2_b").is_ok());
+        assert!(check_profile_name("").is_err());
+        assert!(check_profile_name("../work").is_err());
+        assert!(check_profile_name("a/b").is_err());
+    }
+
+    #[test]
+    fn marks_unversioned_config_dir_as_current() {
+        let dir = TempDir::new();
+
+        check_schema_version(dir.path()).unwrap();
+
+        let version = fs::read_to_string(dir.path().join(VERSION_FILE)).unwrap();
+        assert_eq!(version, CONFIG_SCHEMA_VERSION.to_string());
+        assert!(dir.path().join("servers").is_dir());
+    }
+
+    #[test]
+    fn refuses_config_dir_from_a_newer_build() {
+        let dir = TempDir::new();
+        let newer = (CONFIG_SCHEMA_VERSION + 1).to_string();
+        fs::write(dir.path().join(VERSION_FILE), &newer).unwrap();
+
+        let error = check_schema_version(dir.path()).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            fs::read_to_string(dir.path().join(VERSION_FILE)).unwrap(),
+            newer
+        );
+    }
+
+    #[test]
+    fn refuses_unreadable_version_marker() {
+        let dir = TempDir::new();
+        fs::write(dir.path().join(VERSION_FILE), "one").unwrap();
+
+        let error = check_schema_version(dir.path()).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn uses_config_dir_when_writable() {
+        let dir = TempDir::new();
+        let config_dir = dir.path().join("config");
+
+        let (used, temporary) =
+            writable_config_dir(config_dir.clone(), dir.path().join("fallback")).unwrap();
+
+        assert_eq!(used, config_dir);
+        assert!(!temporary);
+    }
+
+    #[test]
+    fn falls_back_when_config_dir_is_not_writable() {
+        let dir = TempDir::new();
+        // A file in the way fails even for root, unlike permissions
+        let blocker = dir.path().join("blocker");
+        fs::write(&blocker, "").unwrap();
+        let fallback = dir.path().join("fallback");
+
+        let (used, temporary) =
+            writable_config_dir(blocker.join("config"), fallback.clone()).unwrap();
+
+        assert_eq!(used, fallback);
+        assert!(temporary);
+        assert!(fallback.is_dir());
+    }
+
+    #[test]
+    fn names_the_config_file_on_an_unknown_key() {
+        let dir = TempDir::new();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(&path, "prot = 4400\n").unwrap();
+
+        let error = FileConfig::load(dir.path()).err().unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let message = error.to_string();
+        assert!(message.contains(&path.display().to_string()), "{message}");
+        assert!(message.contains("prot"), "{message}");
+    }
+
+    #[test]
+    fn parses_source_timeouts() {
+        let timeouts = parse_source_timeouts("location=8000, spotify = 1500");
+        assert_eq!(timeouts.len(), 2);
+        assert_eq!(timeouts["location"], 8000);
+        assert_eq!(timeouts["spotify"], 1500);
+    }
+
+    #[test]
+    fn skips_invalid_source_timeouts() {
+        let timeouts = parse_source_timeouts("location=soon,spotify,clipboard=-1,url=300,");
+        assert_eq!(timeouts, HashMap::from([("url".to_owned(), 300)]));
+    }
+
+    // Fails with each of `errors` in turn, then succeeds
+    fn failing(errors: Vec<io::ErrorKind>) -> (impl FnMut() -> io::Result<()>, Rc<Cell<u32>>) {
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        let op = move || {
+            let call = counter.get();
+            counter.set(call + 1);
+            match errors.get(call as usize) {
+                Some(&kind) => Err(io::Error::from(kind)),
+                None => Ok(()),
+            }
+        };
+        (op, calls)
+    }
+
+    #[test]
+    fn retries_transient_pid_write_errors() {
+        let (op, calls) = failing(vec![io::ErrorKind::Interrupted, io::ErrorKind::WouldBlock]);
+        assert!(retry_transient(PID_WRITE_RETRIES, op).is_ok());
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn gives_up_after_the_last_retry() {
+        let (op, calls) = failing(vec![io::ErrorKind::Interrupted; 5]);
+        let error = retry_transient(PID_WRITE_RETRIES, op).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+        assert_eq!(calls.get(), PID_WRITE_RETRIES + 1);
+    }
+
+    #[test]
+    fn does_not_retry_other_errors() {
+        let (op, calls) = failing(vec![io::ErrorKind::PermissionDenied]);
+        let error = retry_transient(PID_WRITE_RETRIES, op).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn writes_and_reads_back_the_pid_file() {
+        let dir = TempDir::new();
+        let config = Config::for_test(dir.path(), 4318);
+
+        config.write_pid_file(1234).unwrap();
+
+        assert_eq!(config.read_pid_file(), Some(1234));
+        assert!(dir.path().join("server-4318.pid").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cleanup_all_servers_only_signals_our_servers() {
+        use crate::test_support::spawn_copy_of_sleep;
+        use std::process::Command;
+
+        let dir = TempDir::new();
+        let config = Config::for_test(dir.path(), unused_port());
+        let ours = spawn_copy_of_sleep(&config.get_servers_dir().join("v1").join("server"));
+        let ours_pid = ours.id();
+        // Reaps the server as soon as it's signalled, so it reads as gone
+        let reaper = thread::spawn(move || {
+            let mut ours = ours;
+            ours.wait().unwrap()
+        });
+        let mut foreign = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid_file = |port: u16, content: String| {
+            fs::write(dir.path().join(format!("server-{port}.pid")), content).unwrap()
+        };
+        pid_file(4500, ours_pid.to_string());
+        pid_file(4501, foreign.id().to_string());
+        pid_file(4502, "garbage".to_owned());
+
+        let mut fixes = config.cleanup_all_servers();
+        fixes.sort();
+        let foreign_alive = process_alive(foreign.id());
+        let _ = foreign.kill();
+        let _ = foreign.wait();
+
+        assert!(!reaper.join().unwrap().success());
+        assert!(foreign_alive);
+        assert_eq!(fixes.len(), 3, "{fixes:?}");
+        let not_ours = format!(
+            "removed server-4501.pid without signalling process {}",
+            foreign.id()
+        );
+        assert!(fixes[0].starts_with(&not_ours), "{}", fixes[0]);
+        assert_eq!(fixes[1], "removed unreadable server-4502.pid");
+        assert_eq!(
+            fixes[2],
+            format!("stopped orphaned server {ours_pid} on port 4500")
+        );
+        // Only the servers dir is left
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn skips_port_held_on_every_interface() {
+        let held = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let port = held.local_addr().unwrap().port();
+
+        assert!(!port_available(port));
+        assert!(find_free_port(port).unwrap() > port);
     }
 }
@@ -1,6 +1,399 @@
-use std::io;
-use std::path::PathBuf;
-use std::process::Command;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::server::kill_process;
+use crate::AppState;
+
+// Why a context command failed. Crosses to the frontend as a tagged object,
+// e.g. `{ "kind": "app_not_running", "detail": "Spotify" }`, so it can tell a
+// missing permission apart from a script that broke.
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum ContextError {
+    // The app the source reads from, by process name
+    AppNotRunning(String),
+    // Automation or Accessibility access hasn't been granted
+    PermissionDenied(String),
+    TimedOut,
+    ScriptFailed(String),
+    // Not available on this platform
+    Unsupported,
+    // No context source is registered under this name
+    UnknownSource(String),
+}
+
+impl ContextError {
+    // osascript reports missing Automation access as -1743 and missing
+    // Accessibility access as -1719 or -25211
+    fn from_script_error(message: String) -> Self {
+        let denied = ["-1743", "-1719", "-25211", "not allowed assistive access"]
+            .iter()
+            .any(|marker| message.contains(marker));
+        if denied {
+            ContextError::PermissionDenied(message)
+        } else {
+            ContextError::ScriptFailed(message)
+        }
+    }
+}
+
+impl std::fmt::Display for ContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContextError::AppNotRunning(app) => write!(f, "{app} is not running"),
+            ContextError::PermissionDenied(message) => write!(f, "Permission denied: {message}"),
+            ContextError::TimedOut => write!(f, "Timed out"),
+            ContextError::ScriptFailed(message) => write!(f, "{message}"),
+            ContextError::Unsupported => write!(f, "Not available on this platform"),
+            ContextError::UnknownSource(name) => write!(f, "Unknown context source {name:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ContextError {}
+
+impl From<io::Error> for ContextError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::TimedOut => ContextError::TimedOut,
+            io::ErrorKind::Unsupported => ContextError::Unsupported,
+            _ => ContextError::ScriptFailed(e.to_string()),
+        }
+    }
+}
+
+// Scripts whose output doesn't parse
+impl From<serde_json::Error> for ContextError {
+    fn from(e: serde_json::Error) -> Self {
+        ContextError::ScriptFailed(format!("Unexpected script output: {e}"))
+    }
+}
+
+// A blocking task that panicked or was cancelled
+impl From<tauri::Error> for ContextError {
+    fn from(e: tauri::Error) -> Self {
+        ContextError::ScriptFailed(e.to_string())
+    }
+}
+
+// Tracks the helper processes (osascript, pgrep, CoreLocationCLI) spawned by
+// context commands so they can be drained rather than orphaned on quit, and
+// bounds each one by its source's timeout
+pub struct ContextTasks {
+    children: Mutex<HashSet<u32>>,
+    shutting_down: AtomicBool,
+    default_timeout_ms: u64,
+    source_timeouts: Mutex<HashMap<String, u64>>,
+    // Helper runs and failures per source since launch, for get_metrics
+    counts: Mutex<HashMap<String, SourceCounts>>,
+}
+
+#[derive(Clone, Copy, Default, serde::Serialize)]
+pub struct SourceCounts {
+    pub gathers: u64,
+    pub errors: u64,
+}
+
+impl ContextTasks {
+    pub fn new(default_timeout_ms: u64, source_timeouts: HashMap<String, u64>) -> Self {
+        ContextTasks {
+            children: Mutex::new(HashSet::new()),
+            shutting_down: AtomicBool::new(false),
+            default_timeout_ms,
+            source_timeouts: Mutex::new(source_timeouts),
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn count(&self, source: &str, error: bool) {
+        let mut counts = self.counts.lock().unwrap();
+        let entry = counts.entry(source.to_owned()).or_default();
+        entry.gathers += 1;
+        entry.errors += u64::from(error);
+    }
+
+    fn count_error(&self, source: &str) {
+        let mut counts = self.counts.lock().unwrap();
+        counts.entry(source.to_owned()).or_default().errors += 1;
+    }
+
+    pub fn counts(&self) -> HashMap<String, SourceCounts> {
+        self.counts.lock().unwrap().clone()
+    }
+
+    fn timeout_for(&self, source: &str) -> Duration {
+        let ms = self
+            .source_timeouts
+            .lock()
+            .unwrap()
+            .get(source)
+            .copied()
+            .unwrap_or(self.default_timeout_ms);
+        Duration::from_millis(ms)
+    }
+
+    fn output(&self, source: &str, command: &mut Command) -> io::Result<Output> {
+        self.output_within(source, command, self.timeout_for(source))
+    }
+
+    // Like output, with a timeout chosen by the caller instead of the source's
+    fn output_within(
+        &self,
+        source: &str,
+        command: &mut Command,
+        timeout: Duration,
+    ) -> io::Result<Output> {
+        if self.shutting_down.load(Ordering::SeqCst) {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "App is shutting down",
+            ));
+        }
+
+        let _span = tracing::debug_span!("context_source", source).entered();
+        let started_at = Instant::now();
+
+        let child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .inspect_err(|e| {
+                tracing::warn!(error = %e, "failed to start helper");
+                self.count(source, true);
+            })?;
+        let pid = child.id();
+        self.children.lock().unwrap().insert(pid);
+
+        let output = wait_with_timeout(child, timeout);
+        self.children.lock().unwrap().remove(&pid);
+        self.count(source, output.is_err());
+
+        let elapsed_ms = started_at.elapsed().as_millis() as u64;
+        match &output {
+            Ok(output) => tracing::debug!(elapsed_ms, status = %output.status, "helper finished"),
+            Err(e) => tracing::warn!(elapsed_ms, error = %e, "helper failed"),
+        }
+        output
+    }
+
+    // For a helper that keeps printing results: hands each line of its output
+    // to `on_line` until that returns true or the helper exits, then kills it.
+    // Fails with TimedOut if neither happens within `timeout`. The returned
+    // output carries the exit status and stderr but no stdout.
+    fn stream_lines(
+        &self,
+        source: &str,
+        command: &mut Command,
+        timeout: Duration,
+        mut on_line: impl FnMut(&str) -> bool,
+    ) -> io::Result<Output> {
+        if self.shutting_down.load(Ordering::SeqCst) {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "App is shutting down",
+            ));
+        }
+
+        let _span = tracing::debug_span!("context_source", source).entered();
+        let started_at = Instant::now();
+
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .inspect_err(|e| {
+                tracing::warn!(error = %e, "failed to start helper");
+                self.count(source, true);
+            })?;
+        let pid = child.id();
+        self.children.lock().unwrap().insert(pid);
+
+        let stdout = child.stdout.take();
+        let mut stderr = child.stderr.take();
+        let (line_tx, line_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let Some(stdout) = stdout else {
+                return;
+            };
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if line_tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let stderr_reader = thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(pipe) = stderr.as_mut() {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        });
+
+        let deadline = started_at + timeout;
+        let mut timed_out = false;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match line_rx.recv_timeout(remaining) {
+                Ok(line) if on_line(&line) => break,
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    timed_out = true;
+                    break;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        let _ = child.kill();
+        let status = child.wait();
+        self.children.lock().unwrap().remove(&pid);
+        self.count(source, timed_out);
+
+        let elapsed_ms = started_at.elapsed().as_millis() as u64;
+        if timed_out {
+            tracing::warn!(elapsed_ms, "helper timed out");
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("Timed out after {}ms", timeout.as_millis()),
+            ));
+        }
+        tracing::debug!(elapsed_ms, "helper finished");
+        Ok(Output {
+            status: status?,
+            stdout: Vec::new(),
+            stderr: stderr_reader.join().unwrap_or_default(),
+        })
+    }
+
+    // Stops new helpers from starting, gives running ones up to `timeout` to
+    // finish, then kills whatever is left
+    pub fn drain(&self, timeout: Duration) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline && !self.children.lock().unwrap().is_empty() {
+            thread::sleep(Duration::from_millis(25));
+        }
+
+        for pid in self.children.lock().unwrap().drain() {
+            kill_process(pid);
+        }
+    }
+}
+
+// Short-lived per-key cache for context results that are expensive to read
+pub struct TtlCache<V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, V)>>,
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub fn new(ttl: Duration) -> Self {
+        TtlCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<V> {
+        let entries = self.entries.lock().unwrap();
+        let (stored_at, value) = entries.get(key)?;
+        (stored_at.elapsed() < self.ttl).then(|| value.clone())
+    }
+
+    fn insert(&self, key: String, value: V) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), value));
+    }
+
+    // For a value known to be out of date before its TTL runs out
+    #[cfg(target_os = "macos")]
+    fn remove(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+// Results of the context commands that frontends poll, keyed by source. Each
+// holds whatever type its command returns.
+pub type ContextCache = TtlCache<Arc<dyn std::any::Any + Send + Sync>>;
+
+// Returns the result cached for `source` if it's still fresh, otherwise reads
+// and caches it. `force` skips the cache for a guaranteed fresh read. Errors
+// aren't cached, so a failing source is retried on the next call.
+fn cached<T: Clone + Send + Sync + 'static>(
+    state: &AppState,
+    source: &str,
+    force: Option<bool>,
+    read: impl FnOnce() -> Result<T, ContextError>,
+) -> Result<T, ContextError> {
+    if !force.unwrap_or(false) {
+        let hit = state
+            .context_cache
+            .get(source)
+            .and_then(|value| value.downcast::<T>().ok());
+        if let Some(value) = hit {
+            return Ok(T::clone(&value));
+        }
+    }
+
+    let value = read()?;
+    state
+        .context_cache
+        .insert(source.to_owned(), Arc::new(value.clone()));
+    Ok(value)
+}
+
+fn wait_with_timeout(mut child: Child, timeout: Duration) -> io::Result<Output> {
+    // Drain the pipes on their own threads so a chatty child can't block on a
+    // full pipe while we poll for its exit
+    let mut stdout = child.stdout.take();
+    let mut stderr = child.stderr.take();
+    let stdout_reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(pipe) = stdout.as_mut() {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    });
+    let stderr_reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(pipe) = stderr.as_mut() {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("Timed out after {}ms", timeout.as_millis()),
+            ));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout_reader.join().unwrap_or_default(),
+        stderr: stderr_reader.join().unwrap_or_default(),
+    })
+}
 
 fn get_script_path(script_name: &str) -> PathBuf {
     let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -10,58 +403,242 @@ fn get_script_path(script_name: &str) -> PathBuf {
     path
 }
 
-fn run_script(script_path: &PathBuf) -> Result<String, tauri::Error> {
-    let output = Command::new("osascript")
-        .arg(script_path)
-        .output()
-        .map_err(|e| tauri::Error::Io(e))?;
+fn run_script(
+    tasks: &ContextTasks,
+    source: &str,
+    script_path: &PathBuf,
+) -> Result<String, ContextError> {
+    run_script_with_args(tasks, source, script_path, &[])
+}
+
+fn run_script_with_args(
+    tasks: &ContextTasks,
+    source: &str,
+    script_path: &PathBuf,
+    args: &[&str],
+) -> Result<String, ContextError> {
+    let mut command = Command::new("osascript");
+    // JavaScript for Automation scripts need the language spelled out
+    if script_path.extension().is_some_and(|ext| ext == "js") {
+        command.args(["-l", "JavaScript"]);
+    }
+
+    let output = tasks.output(source, command.arg(script_path).args(args))?;
 
     if !output.status.success() {
-        return Err(tauri::Error::Io(io::Error::new(
-            io::ErrorKind::Other,
-            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
-        )));
+        tasks.count_error(source);
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(ContextError::from_script_error(message));
     }
 
-    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    Ok(script_output(&output.stdout))
 }
 
-fn run_app_script(app_process_name: &str, script_path: &PathBuf) -> Result<String, tauri::Error> {
-    let status = Command::new("/usr/bin/pgrep")
-        .args(["-x", app_process_name])
-        .status()
-        .map_err(tauri::Error::Io)?;
+// Only the end is trimmed: scripts that print one field per line start with an
+// empty line when their first field is empty, and dropping it would shift
+// every field after it
+fn script_output(stdout: &[u8]) -> String {
+    String::from_utf8_lossy(stdout).trim_end().to_owned()
+}
 
-    if !status.success() {
-        return Err(tauri::Error::Io(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("{app_process_name} is not running"),
-        )));
+// Whether this app may script other apps, tested by asking System Events for
+// something harmless. The first call shows the Automation prompt if the user
+// hasn't answered it yet.
+pub fn automation_granted(tasks: &ContextTasks) -> bool {
+    let mut command = Command::new("osascript");
+    command.args([
+        "-e",
+        "tell application \"System Events\" to count application processes",
+    ]);
+    tasks
+        .output("automation", &mut command)
+        .is_ok_and(|output| output.status.success())
+}
+
+fn run_app_script(
+    tasks: &ContextTasks,
+    source: &str,
+    app_process_name: &str,
+    script_path: &PathBuf,
+) -> Result<String, ContextError> {
+    let output = tasks.output(
+        source,
+        Command::new("/usr/bin/pgrep").args(["-x", app_process_name]),
+    )?;
+
+    if !output.status.success() {
+        return Err(ContextError::AppNotRunning(app_process_name.to_owned()));
     }
 
-    run_script(script_path)
+    run_script(tasks, source, script_path)
+}
+
+// Arc's URL whether or not it's frontmost. Kept for existing callers; new code
+// should use active_browser_url.
+#[tauri::command]
+pub fn active_arc_url(
+    state: tauri::State<AppState>,
+    force: Option<bool>,
+) -> Result<String, ContextError> {
+    cached(&state, "arc", force, || {
+        let script_path = get_script_path("get_arc_url.applescript");
+        run_app_script(&state.context_tasks, "arc", "Arc", &script_path)
+    })
+}
+
+const SAFARI_BUNDLE_ID: &str = "com.apple.Safari";
+const ARC_BUNDLE_ID: &str = "company.thebrowser.Browser";
+
+// Chromium browsers share Chrome's `execute javascript` scripting command
+const CHROMIUM_BUNDLE_IDS: &[&str] = &[
+    ARC_BUNDLE_ID,
+    "com.google.Chrome",
+    "com.brave.Browser",
+    "com.microsoft.edgemac",
+];
+
+pub fn is_browser(bundle_id: &str) -> bool {
+    bundle_id == SAFARI_BUNDLE_ID || CHROMIUM_BUNDLE_IDS.contains(&bundle_id)
 }
 
+#[derive(Clone, serde::Serialize)]
+pub struct BrowserUrl {
+    browser: String,
+    url: String,
+}
+
+// None when the frontmost app isn't a supported browser or has no windows
 #[tauri::command]
-pub fn active_arc_url() -> Result<String, tauri::Error> {
-    let script_path = get_script_path("get_arc_url.applescript");
-    run_app_script("Arc", &script_path)
+pub fn active_browser_url(
+    state: tauri::State<AppState>,
+    force: Option<bool>,
+) -> Result<Option<BrowserUrl>, ContextError> {
+    cached(&state, "browser_url", force, || {
+        let tasks = &state.context_tasks;
+        let focused_app = focused_app(tasks)?;
+        browser_url(tasks, &focused_app)
+    })
+}
+
+fn browser_url(
+    tasks: &ContextTasks,
+    focused_app: &FocusedAppInfo,
+) -> Result<Option<BrowserUrl>, ContextError> {
+    if !is_browser(&focused_app.bundle_id) {
+        return Ok(None);
+    }
+
+    let script_path = get_script_path("get_browser_url.js");
+    let url = run_script_with_args(
+        tasks,
+        "browser_url",
+        &script_path,
+        &[&focused_app.bundle_id],
+    )?;
+    if url.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(BrowserUrl {
+        browser: focused_app.name.clone(),
+        url,
+    }))
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
+pub struct PageScrollContext {
+    scroll_y: f64,
+    nearest_heading: Option<String>,
+}
+
+// Whether a script failed because the browser doesn't allow JavaScript from
+// Apple Events. Safari and Chrome both name the setting in their error.
+fn javascript_disabled(message: &str) -> bool {
+    message.contains("Allow JavaScript from Apple Events")
+        || message.contains("JavaScript through AppleScript is turned off")
+}
+
+// None when no supported browser is frontmost or it doesn't allow JavaScript
+// from Apple Events
+#[tauri::command]
+pub fn get_page_scroll_context(
+    state: tauri::State<AppState>,
+) -> Result<Option<PageScrollContext>, ContextError> {
+    let tasks = &state.context_tasks;
+    let focused_app = focused_app(tasks)?;
+    let bundle_id = focused_app.bundle_id.as_str();
+    if !is_browser(bundle_id) {
+        return Ok(None);
+    }
+
+    let script_path = get_script_path("get_page_scroll_context.js");
+    let output_str = match run_script_with_args(tasks, "page_scroll", &script_path, &[bundle_id]) {
+        Ok(output) => output,
+        Err(ContextError::ScriptFailed(message) | ContextError::PermissionDenied(message))
+            if javascript_disabled(&message) =>
+        {
+            return Ok(None)
+        }
+        Err(e) => return Err(e),
+    };
+    if output_str.is_empty() {
+        return Ok(None);
+    }
+
+    let context: PageScrollContext = serde_json::from_str(&output_str)?;
+    Ok(Some(context))
+}
+
+#[derive(Clone, serde::Serialize)]
 pub struct SpotifyTrackInfo {
     artist: String,
     track: String,
+    // False when paused or stopped; the track is then the last one loaded
+    is_playing: bool,
+    position_seconds: Option<f64>,
+    duration_seconds: Option<f64>,
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct QueuedTrack {
+    artist: String,
+    track: String,
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct PlaybackQueue {
+    source: String,
+    // Playlist for Apple Music; Spotify only exposes the current album
+    collection: Option<String>,
+    upcoming: Vec<QueuedTrack>,
+}
+
+#[derive(Clone, serde::Serialize)]
 pub struct FocusedAppInfo {
     name: String,
     #[serde(rename = "bundleId")]
     bundle_id: String,
+    pid: u32,
+    // None without Accessibility access or when the app has no windows
+    #[serde(rename = "windowTitle")]
+    window_title: Option<String>,
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
+pub struct IdeStatus {
+    app: String,
+    scheme: Option<String>,
+    building: bool,
+    last_result: Option<String>,
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct RecentDocument {
+    name: String,
+    path: String,
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub struct LocationInfo {
     #[serde(rename = "time_local")]
     time_local: String,
@@ -95,38 +672,1521 @@ pub struct LocationInfo {
     #[serde(rename = "v_accuracy")]
     v_accuracy: String,
     country: Option<String>,
+    // Horizontal accuracy in meters, parsed from h_accuracy
+    #[serde(skip_deserializing)]
+    accuracy_meters: Option<f64>,
+    // Set when CoreLocation failed and this is the last known location, older
+    // than the max age
+    #[serde(skip_deserializing)]
+    stale: bool,
+}
+
+// How long get_location may take and how precise a fix it needs. Both are
+// optional: without a timeout the location source's one applies, and without
+// a desired accuracy the first fix is taken.
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+pub struct LocationOptions {
+    timeout_ms: Option<u64>,
+    // Horizontal accuracy in meters. A larger value is satisfied sooner, by a
+    // cached fix or the first rough one CoreLocation reports.
+    desired_accuracy: Option<f64>,
+}
+
+impl LocationOptions {
+    fn accepts(&self, location: &LocationInfo) -> bool {
+        match (self.desired_accuracy, location.accuracy_meters) {
+            (None, _) => true,
+            (Some(desired), Some(accuracy)) => accuracy <= desired,
+            (Some(_), None) => false,
+        }
+    }
+}
+
+// The last location CoreLocation returned and when, so repeated calls don't
+// each wait on it or prompt for permission again
+#[derive(Default)]
+pub struct LocationCache {
+    last: Mutex<Option<(Instant, LocationInfo)>>,
+}
+
+#[tauri::command]
+pub fn get_spotify_track(
+    state: tauri::State<AppState>,
+    force: Option<bool>,
+) -> Result<SpotifyTrackInfo, ContextError> {
+    cached(&state, "spotify", force, || {
+        let script_path = get_script_path("get_spotify_track.applescript");
+        let output_str = run_app_script(&state.context_tasks, "spotify", "Spotify", &script_path)?;
+        parse_spotify_track(&output_str)
+    })
+}
+
+// Artist, track, whether it's playing, then position and duration in ms, one
+// per line. Fields are read by position, so an empty one (podcasts and ads
+// often have no artist) is still an empty line.
+fn parse_spotify_track(output: &str) -> Result<SpotifyTrackInfo, ContextError> {
+    let lines: Vec<&str> = output.lines().collect();
+    let [artist, track, is_playing, rest @ ..] = lines.as_slice() else {
+        return Err(ContextError::ScriptFailed(format!(
+            "Unexpected script output: {output:?}"
+        )));
+    };
+    let seconds = |index: usize| {
+        rest.get(index)
+            .and_then(|ms| ms.trim().parse::<f64>().ok())
+            .map(|ms| ms / 1000.0)
+    };
+
+    Ok(SpotifyTrackInfo {
+        artist: artist.to_string(),
+        track: track.to_string(),
+        is_playing: *is_playing == "true",
+        position_seconds: seconds(0),
+        duration_seconds: seconds(1),
+    })
+}
+
+#[tauri::command]
+pub fn get_playback_queue(
+    state: tauri::State<AppState>,
+    force: Option<bool>,
+) -> Result<Option<PlaybackQueue>, ContextError> {
+    cached(&state, "playback_queue", force, || playback_queue(&state))
+}
+
+fn playback_queue(state: &AppState) -> Result<Option<PlaybackQueue>, ContextError> {
+    let tasks = &state.context_tasks;
+    let players = [
+        ("spotify", "Spotify", "get_spotify_queue.applescript"),
+        ("apple_music", "Music", "get_music_queue.applescript"),
+    ];
+
+    // First player that is running and actually playing wins
+    let queue = players.iter().find_map(|(source, process_name, script)| {
+        let script_path = get_script_path(script);
+        let output_str = run_app_script(tasks, source, process_name, &script_path).ok()?;
+        parse_playback_queue(&output_str)
+    });
+
+    Ok(queue)
+}
+
+// The scripts' JSON, with names escaped by their jsonEscape handler
+fn parse_playback_queue(output: &str) -> Option<PlaybackQueue> {
+    serde_json::from_str(output).ok()
 }
 
+#[derive(Clone, serde::Serialize)]
+pub struct NowPlaying {
+    source: &'static str,
+    track: String,
+    artist: String,
+    album: Option<String>,
+}
+
+// Spotify if it's playing, otherwise Apple Music; None when neither is
 #[tauri::command]
-pub fn get_spotify_track() -> Result<SpotifyTrackInfo, tauri::Error> {
-    let script_path = get_script_path("get_spotify_track.applescript");
-    let output_str = run_app_script("Spotify", &script_path)?;
+pub fn get_now_playing(
+    state: tauri::State<AppState>,
+    force: Option<bool>,
+) -> Result<Option<NowPlaying>, ContextError> {
+    cached(&state, "now_playing", force, || now_playing(&state))
+}
+
+fn now_playing(state: &AppState) -> Result<Option<NowPlaying>, ContextError> {
+    let tasks = &state.context_tasks;
+    let players = [
+        ("spotify", "Spotify", "get_spotify_now_playing.applescript"),
+        ("apple_music", "Music", "get_music_now_playing.applescript"),
+    ];
 
-    let track_info: SpotifyTrackInfo = serde_json::from_str(&output_str)?;
+    let now_playing = players.iter().find_map(|(source, process_name, script)| {
+        let script_path = get_script_path(script);
+        let output_str = run_app_script(tasks, source, process_name, &script_path).ok()?;
+        let mut lines = output_str.lines();
+        let track = lines.next()?.to_owned();
+        let artist = lines.next().unwrap_or_default().to_owned();
+        let album = lines.next().filter(|album| !album.is_empty());
+        Some(NowPlaying {
+            source,
+            track,
+            artist,
+            album: album.map(str::to_owned),
+        })
+    });
 
-    Ok(track_info)
+    Ok(now_playing)
 }
 
 #[tauri::command]
-pub fn get_focused_app() -> Result<FocusedAppInfo, tauri::Error> {
+pub fn get_focused_app(
+    state: tauri::State<AppState>,
+    force: Option<bool>,
+) -> Result<FocusedAppInfo, ContextError> {
+    cached(&state, "focused_app", force, || {
+        focused_app(&state.context_tasks)
+    })
+}
+
+pub fn focused_app(tasks: &ContextTasks) -> Result<FocusedAppInfo, ContextError> {
     let script_path = get_script_path("get_focused_app.applescript");
-    let output_str = run_script(&script_path)?;
+    let output_str = run_script(tasks, "focused_app", &script_path)?;
+
+    let mut lines = output_str.lines();
+    let (Some(name), Some(bundle_id), Some(pid)) = (lines.next(), lines.next(), lines.next())
+    else {
+        return Err(ContextError::ScriptFailed(format!(
+            "Unexpected script output: {output_str:?}"
+        )));
+    };
+    let pid = pid
+        .trim()
+        .parse()
+        .map_err(|_| ContextError::ScriptFailed(format!("Unexpected process id {pid:?}")))?;
+    let window_title = lines.next().filter(|title| !title.is_empty());
+
+    Ok(FocusedAppInfo {
+        name: name.to_owned(),
+        bundle_id: bundle_id.to_owned(),
+        pid,
+        window_title: window_title.map(str::to_owned),
+    })
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {
+    // Defined in src/app_focus.m
+    fn thoughts_observe_app_focus(
+        callback: extern "C" fn(*const std::ffi::c_char, *const std::ffi::c_char, i32),
+    );
+    fn thoughts_stop_observing_app_focus();
+}
+
+// The observer's callback has no context pointer, so the handle is kept here
+#[cfg(target_os = "macos")]
+static APP_FOCUS_HANDLE: std::sync::OnceLock<tauri::AppHandle> = std::sync::OnceLock::new();
+
+// Bundle id of the frontmost app as last reported by the observer
+#[cfg(target_os = "macos")]
+static FRONTMOST_BUNDLE_ID: Mutex<Option<String>> = Mutex::new(None);
+
+// The frontmost app's bundle id without running a script, for callers that
+// can't wait on one. None until watch_app_focus has reported an app.
+#[cfg(target_os = "macos")]
+pub fn frontmost_bundle_id() -> Option<String> {
+    FRONTMOST_BUNDLE_ID.lock().unwrap().clone()
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn frontmost_bundle_id() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "macos")]
+extern "C" fn app_activated(
+    name: *const std::ffi::c_char,
+    bundle_id: *const std::ffi::c_char,
+    pid: i32,
+) {
+    use tauri::{Emitter, Manager};
+
+    let Some(app) = APP_FOCUS_HANDLE.get() else {
+        return;
+    };
+    let text = |ptr: *const std::ffi::c_char| {
+        if ptr.is_null() {
+            return String::new();
+        }
+        unsafe { std::ffi::CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    let info = FocusedAppInfo {
+        name: text(name),
+        bundle_id: text(bundle_id),
+        pid: pid as u32,
+        // Reading it means running a script, which get_focused_app does on
+        // demand
+        window_title: None,
+    };
+
+    *FRONTMOST_BUNDLE_ID.lock().unwrap() = Some(info.bundle_id.clone());
+    app.state::<AppState>().context_cache.remove("focused_app");
+    let _ = app.emit("app-focus-changed", &info);
+}
+
+// Emits app-focus-changed with the new app whenever the frontmost one
+// changes, so the frontend doesn't have to poll get_focused_app
+#[cfg(target_os = "macos")]
+pub fn watch_app_focus(app: tauri::AppHandle) {
+    if APP_FOCUS_HANDLE.set(app).is_err() {
+        return;
+    }
+    unsafe { thoughts_observe_app_focus(app_activated) };
+}
+
+#[cfg(target_os = "macos")]
+pub fn stop_watching_app_focus() {
+    unsafe { thoughts_stop_observing_app_focus() };
+}
 
-    let app_info: FocusedAppInfo = serde_json::from_str(&output_str)?;
+#[derive(Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct WindowBounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+// Frame of the frontmost app's front window, in points. None when the app has
+// no windows.
+pub fn front_window_bounds(tasks: &ContextTasks) -> Result<Option<WindowBounds>, ContextError> {
+    let script_path = get_script_path("get_front_window_bounds.applescript");
+    let output_str = run_script(tasks, "window_bounds", &script_path)?;
+    if output_str.is_empty() {
+        return Ok(None);
+    }
+
+    let bounds: WindowBounds = serde_json::from_str(&output_str)?;
+
+    Ok(Some(bounds))
+}
+
+#[tauri::command]
+pub fn get_active_window_bounds(
+    state: tauri::State<AppState>,
+) -> Result<Option<WindowBounds>, ContextError> {
+    front_window_bounds(&state.context_tasks)
+}
+
+const WINDOW_TRACKING_INTERVAL: Duration = Duration::from_millis(300);
+
+// While enabled, emits `active-window-bounds` whenever the frontmost window
+// moves, resizes or changes. Each enable bumps the generation, which retires
+// any tracker started before it.
+#[tauri::command]
+pub fn set_window_bounds_tracking(
+    app: tauri::AppHandle,
+    state: tauri::State<AppState>,
+    enabled: bool,
+) {
+    use tauri::{Emitter, Manager};
+
+    let generation = state.window_tracking.fetch_add(1, Ordering::SeqCst) + 1;
+    if !enabled {
+        return;
+    }
+
+    thread::spawn(move || {
+        let mut last_bounds = None;
+        loop {
+            let state = app.state::<AppState>();
+            if state.window_tracking.load(Ordering::SeqCst) != generation {
+                return;
+            }
+
+            if let Ok(bounds) = front_window_bounds(&state.context_tasks) {
+                if bounds != last_bounds {
+                    let _ = app.emit("active-window-bounds", &bounds);
+                    last_bounds = bounds;
+                }
+            }
+            thread::sleep(WINDOW_TRACKING_INTERVAL);
+        }
+    });
+}
+
+const OBSIDIAN_BUNDLE_ID: &str = "md.obsidian";
+const NOTION_BUNDLE_ID: &str = "notion.id";
+
+#[derive(serde::Serialize)]
+pub struct NotesAppContext {
+    app: String,
+    workspace_or_vault: Option<String>,
+    note_title: Option<String>,
+}
+
+fn front_window_title(tasks: &ContextTasks) -> Result<String, ContextError> {
+    let script_path = get_script_path("get_front_window_title.applescript");
+    run_script(tasks, "window_title", &script_path)
+}
+
+// Whether the frontmost app's front window is in fullscreen, as for a
+// presentation or a full-screen video call
+pub fn front_window_fullscreen(tasks: &ContextTasks) -> Result<bool, ContextError> {
+    let script_path = get_script_path("get_front_window_fullscreen.applescript");
+    Ok(run_script(tasks, "window_fullscreen", &script_path)? == "true")
+}
+
+// Obsidian titles its windows "<note> - <vault> - Obsidian v1.x.y"
+fn parse_obsidian_title(title: &str) -> NotesAppContext {
+    let mut parts: Vec<&str> = title.split(" - ").collect();
+    if parts
+        .last()
+        .is_some_and(|last| last.starts_with("Obsidian"))
+    {
+        parts.pop();
+    }
+    let vault = (parts.len() > 1).then(|| parts.pop()).flatten();
+    let note = parts.join(" - ");
+
+    NotesAppContext {
+        app: "Obsidian".to_string(),
+        workspace_or_vault: vault.map(str::to_owned),
+        note_title: (!note.is_empty()).then_some(note),
+    }
+}
 
-    Ok(app_info)
+// Page URLs look like notion.so/<workspace>/<Page-Title-<32 hex id>>, with the
+// workspace segment missing for some shared pages
+fn parse_notion_url(url: &str) -> Option<NotesAppContext> {
+    let url = tauri::Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    if host != "notion.so" && !host.ends_with(".notion.so") {
+        return None;
+    }
+
+    let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+    let (workspace, page) = match segments[..] {
+        [workspace, page, ..] => (Some(workspace), Some(page)),
+        [page] => (None, Some(page)),
+        _ => (None, None),
+    };
+
+    let note_title = page.map(|page| {
+        let title = match page.rsplit_once('-') {
+            Some((title, id)) if id.len() == 32 && id.chars().all(|c| c.is_ascii_hexdigit()) => {
+                title
+            }
+            _ => page,
+        };
+        title.replace('-', " ")
+    });
+
+    Some(NotesAppContext {
+        app: "Notion".to_string(),
+        workspace_or_vault: workspace.map(str::to_owned),
+        note_title: note_title.filter(|title| !title.is_empty()),
+    })
 }
 
+// None unless Obsidian, Notion, or Notion open in Arc is frontmost
 #[tauri::command]
-pub fn get_location() -> Result<LocationInfo, tauri::Error> {
-    let output = Command::new("/opt/homebrew/bin/CoreLocationCLI")
-        .arg("--json")
-        .output()
-        .map_err(|e| tauri::Error::Io(e))?;
+pub fn get_notes_app_context(
+    state: tauri::State<AppState>,
+) -> Result<Option<NotesAppContext>, ContextError> {
+    let tasks = &state.context_tasks;
+    let focused_app = focused_app(tasks)?;
+
+    let context = match focused_app.bundle_id.as_str() {
+        OBSIDIAN_BUNDLE_ID => Some(parse_obsidian_title(&front_window_title(tasks)?)),
+        // The desktop app only puts the page title in the window title
+        NOTION_BUNDLE_ID => {
+            let title = front_window_title(tasks)?;
+            Some(NotesAppContext {
+                app: "Notion".to_string(),
+                workspace_or_vault: None,
+                note_title: (!title.is_empty()).then_some(title),
+            })
+        }
+        ARC_BUNDLE_ID => {
+            let script_path = get_script_path("get_arc_url.applescript");
+            let url = run_app_script(tasks, "arc", "Arc", &script_path)?;
+            parse_notion_url(&url)
+        }
+        _ => None,
+    };
+
+    Ok(context)
+}
+
+const MAIL_BUNDLE_ID: &str = "com.apple.mail";
 
-    let output_str = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+// Gmail views that aren't an open message
+const GMAIL_FOLDERS: &[&str] = &[
+    "Inbox",
+    "Starred",
+    "Snoozed",
+    "Important",
+    "Sent Mail",
+    "Drafts",
+    "All Mail",
+    "Spam",
+    "Trash",
+    "Search results",
+];
+
+#[derive(serde::Serialize)]
+pub struct EmailContext {
+    app: String,
+    subject: String,
+    // Webmail titles don't include the sender
+    from: Option<String>,
+}
+
+// Gmail titles a message's tab "<subject> - <account> - Gmail" and Outlook
+// "<subject> - <account> - Outlook"; folder views use the same pattern with the
+// folder name in place of the subject
+fn parse_webmail_title(title: &str) -> Option<EmailContext> {
+    let mut parts: Vec<&str> = title.split(" - ").collect();
+    let app = match parts.pop()? {
+        "Gmail" => "Gmail",
+        last if last.starts_with("Outlook") => "Outlook",
+        _ => return None,
+    };
+    // Drop the account
+    parts.pop()?;
+    let subject = parts.join(" - ");
+
+    // "Inbox (3)" and friends
+    let view = subject.split(" (").next().unwrap_or_default();
+    if subject.is_empty() || GMAIL_FOLDERS.contains(&view) || view == "Mail" {
+        return None;
+    }
+
+    Some(EmailContext {
+        app: app.to_string(),
+        subject,
+        from: None,
+    })
+}
 
-    let location_info: LocationInfo = serde_json::from_str(&output_str)?;
+// None unless Mail.app or a Gmail/Outlook tab is frontmost with a message open
+#[tauri::command]
+pub fn get_email_context(
+    state: tauri::State<AppState>,
+) -> Result<Option<EmailContext>, ContextError> {
+    if !state.config.email_context_enabled() {
+        return Ok(None);
+    }
+
+    let tasks = &state.context_tasks;
+    let focused_app = focused_app(tasks)?;
+
+    if focused_app.bundle_id == MAIL_BUNDLE_ID {
+        let script_path = get_script_path("get_mail_message.applescript");
+        let output = run_app_script(tasks, "mail", "Mail", &script_path)?;
+        return Ok(parse_mail_message(&output));
+    }
+
+    if is_browser(&focused_app.bundle_id) {
+        return Ok(parse_webmail_title(&front_window_title(tasks)?));
+    }
+
+    Ok(None)
+}
+
+// Subject and sender, one per line; either can be empty. No output means no
+// message is selected.
+fn parse_mail_message(output: &str) -> Option<EmailContext> {
+    if output.is_empty() {
+        return None;
+    }
+    let mut lines = output.lines();
+    let subject = lines.next().unwrap_or_default();
+    let from = lines.next().unwrap_or_default();
+    Some(EmailContext {
+        app: "Mail".to_string(),
+        subject: subject.to_owned(),
+        from: (!from.is_empty()).then(|| from.to_owned()),
+    })
+}
+
+#[tauri::command]
+pub fn get_ide_status(state: tauri::State<AppState>) -> Result<Option<IdeStatus>, ContextError> {
+    let tasks = &state.context_tasks;
+    let focused_app = focused_app(tasks)?;
 
+    // Only Xcode exposes build state through its scripting dictionary
+    if focused_app.bundle_id != "com.apple.dt.Xcode" {
+        return Ok(None);
+    }
+
+    let script_path = get_script_path("get_xcode_status.applescript");
+    let status = run_app_script(tasks, "ide", "Xcode", &script_path)
+        .ok()
+        .and_then(|output_str| parse_ide_status(&output_str));
+
+    Ok(status)
+}
+
+// The script's JSON, with names escaped by its jsonEscape handler
+fn parse_ide_status(output: &str) -> Option<IdeStatus> {
+    serde_json::from_str(output).ok()
+}
+
+pub const TERMINAL_BUNDLE_IDS: &[&str] = &[
+    "com.apple.Terminal",
+    "com.googlecode.iterm2",
+    "dev.warp.Warp-Stable",
+    "com.mitchellh.ghostty",
+    "org.alacritty",
+    "net.kovidgoyal.kitty",
+    "com.github.wez.wezterm",
+];
+
+// Only the tail of the history file is read; the last command is always there
+const HISTORY_TAIL_BYTES: u64 = 64 * 1024;
+
+#[tauri::command]
+pub fn get_last_terminal_command(
+    state: tauri::State<AppState>,
+) -> Result<Option<String>, ContextError> {
+    if !state.config.terminal_history_enabled() {
+        return Ok(None);
+    }
+
+    let tasks = &state.context_tasks;
+    let focused_app = focused_app(tasks)?;
+    if !TERMINAL_BUNDLE_IDS.contains(&focused_app.bundle_id.as_str()) {
+        return Ok(None);
+    }
+
+    // iTerm2 knows the exact command for the active session, but only with
+    // shell integration installed; otherwise fall back to the history file
+    if focused_app.bundle_id == "com.googlecode.iterm2" {
+        let script_path = get_script_path("get_iterm_last_command.applescript");
+        if let Ok(command) = run_app_script(tasks, "terminal", "iTerm2", &script_path) {
+            if !command.is_empty() {
+                return Ok(Some(command));
+            }
+        }
+    }
+
+    Ok(last_history_command())
+}
+
+fn last_history_command() -> Option<String> {
+    let home_dir = dirs::home_dir()?;
+    let candidates = std::env::var("HISTFILE")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain([
+            home_dir.join(".zsh_history"),
+            home_dir.join(".bash_history"),
+        ]);
+
+    // Prefer whichever history file was written to most recently
+    let history_path = candidates
+        .filter_map(|path| {
+            let modified = path.metadata().ok()?.modified().ok()?;
+            Some((modified, path))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)?;
+
+    let mut file = File::open(history_path).ok()?;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(HISTORY_TAIL_BYTES)))
+        .ok()?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).ok()?;
+
+    String::from_utf8_lossy(&buf)
+        .lines()
+        .rev()
+        .map(str::trim)
+        // Skip bash timestamp comments (`#1700000000`)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            // zsh extended history: `: <timestamp>:<duration>;<command>`
+            match line
+                .strip_prefix(": ")
+                .and_then(|rest| rest.split_once(';'))
+            {
+                Some((_, command)) => command.to_owned(),
+                None => line.to_owned(),
+            }
+        })
+        .next()
+}
+
+#[tauri::command]
+pub fn get_recent_documents(
+    state: tauri::State<AppState>,
+    bundle_id: Option<String>,
+) -> Result<Vec<RecentDocument>, ContextError> {
+    let bundle_id = match bundle_id {
+        Some(bundle_id) => bundle_id,
+        None => match focused_app(&state.context_tasks) {
+            Ok(app) => app.bundle_id,
+            Err(_) => return Ok(Vec::new()),
+        },
+    };
+
+    Ok(recent_documents(&state, bundle_id))
+}
+
+fn recent_documents(state: &AppState, bundle_id: String) -> Vec<RecentDocument> {
+    if let Some(documents) = state.recent_documents.get(&bundle_id) {
+        return documents;
+    }
+
+    let script_path = get_script_path("get_recent_documents.js");
+    let documents: Vec<RecentDocument> = run_script_with_args(
+        &state.context_tasks,
+        "recent_documents",
+        &script_path,
+        &[&bundle_id],
+    )
+    .ok()
+    .and_then(|output_str| serde_json::from_str(&output_str).ok())
+    .unwrap_or_default();
+
+    state.recent_documents.insert(bundle_id, documents.clone());
+    documents
+}
+
+// Huge diffs still report full totals, but only list this many files
+const MAX_CHANGED_FILES: usize = 50;
+
+#[derive(serde::Serialize)]
+pub struct GitDiffSummary {
+    files_changed: usize,
+    insertions: u64,
+    deletions: u64,
+    changed_files: Vec<String>,
+}
+
+fn git_output(tasks: &ContextTasks, dir: &Path, args: &[&str]) -> Option<String> {
+    let output = tasks
+        .output("git", Command::new("git").arg("-C").arg(dir).args(args))
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Uncommitted changes (staged and unstaged) in the repo containing `path`, or
+// when no path is given, the repo of the focused app's most recent document.
+// None when that isn't inside a repo or git isn't installed.
+#[tauri::command]
+pub fn get_git_diff_summary(
+    state: tauri::State<AppState>,
+    path: Option<String>,
+) -> Result<Option<GitDiffSummary>, ContextError> {
+    let tasks = &state.context_tasks;
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let Ok(app) = focused_app(tasks) else {
+                return Ok(None);
+            };
+            match recent_documents(&state, app.bundle_id).into_iter().next() {
+                Some(document) => PathBuf::from(document.path),
+                None => return Ok(None),
+            }
+        }
+    };
+    let dir = if path.is_dir() {
+        path.as_path()
+    } else {
+        path.parent().unwrap_or(&path)
+    };
+
+    let Some(root) = git_output(tasks, dir, &["rev-parse", "--show-toplevel"]) else {
+        return Ok(None);
+    };
+    let root = PathBuf::from(root.trim());
+
+    // A repo without any commits has no HEAD to diff against
+    let Some(numstat) = git_output(tasks, &root, &["diff", "HEAD", "--numstat"])
+        .or_else(|| git_output(tasks, &root, &["diff", "--numstat"]))
+    else {
+        return Ok(None);
+    };
+
+    let mut summary = GitDiffSummary {
+        files_changed: 0,
+        insertions: 0,
+        deletions: 0,
+        changed_files: Vec::new(),
+    };
+    for line in numstat.lines() {
+        let mut fields = line.splitn(3, '\t');
+        let (Some(added), Some(removed), Some(file)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        // Binary files report "-" for both counts
+        summary.insertions += added.parse::<u64>().unwrap_or(0);
+        summary.deletions += removed.parse::<u64>().unwrap_or(0);
+        summary.files_changed += 1;
+        if summary.changed_files.len() < MAX_CHANGED_FILES {
+            summary.changed_files.push(file.to_owned());
+        }
+    }
+
+    Ok(Some(summary))
+}
+
+#[cfg(target_os = "macos")]
+#[tauri::command]
+pub fn get_accent_color(state: tauri::State<AppState>) -> Result<String, ContextError> {
+    read_accent_color(&state.context_tasks)
+}
+
+#[cfg(not(target_os = "macos"))]
+#[tauri::command]
+pub fn get_accent_color() -> Result<String, ContextError> {
+    Err(ContextError::Unsupported)
+}
+
+#[cfg(target_os = "macos")]
+fn read_accent_color(tasks: &ContextTasks) -> Result<String, ContextError> {
+    let script_path = get_script_path("get_accent_color.js");
+    run_script(tasks, "accent_color", &script_path)
+}
+
+// Emits `accent-color-changed` whenever the system accent color changes.
+// The global preferences plist is rewritten on appearance changes, so its
+// mtime is polled cheaply and the color only re-read when it moves.
+#[cfg(target_os = "macos")]
+pub fn watch_accent_color(app: tauri::AppHandle) {
+    use tauri::{Emitter, Manager};
+
+    let Some(prefs_path) =
+        dirs::home_dir().map(|home| home.join("Library/Preferences/.GlobalPreferences.plist"))
+    else {
+        return;
+    };
+    let modified = move || prefs_path.metadata().and_then(|m| m.modified()).ok();
+
+    thread::spawn(move || {
+        let mut last_modified = modified();
+        let mut last_color = read_accent_color(&app.state::<AppState>().context_tasks).ok();
+
+        loop {
+            thread::sleep(Duration::from_secs(2));
+
+            let current_modified = modified();
+            if current_modified == last_modified {
+                continue;
+            }
+            last_modified = current_modified;
+
+            let Ok(color) = read_accent_color(&app.state::<AppState>().context_tasks) else {
+                continue;
+            };
+            if last_color.as_ref() != Some(&color) {
+                let _ = app.emit("accent-color-changed", &color);
+                last_color = Some(color);
+            }
+        }
+    });
+}
+
+// Seconds since the last keyboard or mouse input anywhere on the system
+#[cfg(target_os = "macos")]
+#[tauri::command]
+pub fn get_idle_seconds() -> Result<f64, ContextError> {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventSourceSecondsSinceLastEventType(state_id: i32, event_type: u32) -> f64;
+    }
+    // kCGEventSourceStateHIDSystemState and kCGAnyInputEventType
+    const HID_SYSTEM_STATE: i32 = 1;
+    const ANY_INPUT_EVENT: u32 = !0;
+
+    Ok(unsafe { CGEventSourceSecondsSinceLastEventType(HID_SYSTEM_STATE, ANY_INPUT_EVENT) })
+}
+
+#[cfg(not(target_os = "macos"))]
+#[tauri::command]
+pub fn get_idle_seconds() -> Result<f64, ContextError> {
+    Err(ContextError::Unsupported)
+}
+
+// Longest side of a window capture, in pixels. Retina windows come out at
+// twice their size in points, which gets large quickly.
+#[cfg(target_os = "macos")]
+const MAX_CAPTURE_DIMENSION: u32 = 1600;
+
+// Screenshot of the focused app's front window, saved as a PNG in the
+// screenshots dir (where cleanup_old_artifacts finds it) and returned as a
+// path. None when the app has no window on screen.
+#[tauri::command]
+pub async fn capture_focused_window(
+    app: tauri::AppHandle,
+) -> Result<Option<PathBuf>, ContextError> {
+    use tauri::Manager;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        capture_window(&state, &state.config.get_screenshots_dir())
+    })
+    .await?
+}
+
+#[cfg(target_os = "macos")]
+fn capture_window(state: &AppState, dir: &Path) -> Result<Option<PathBuf>, ContextError> {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGPreflightScreenCaptureAccess() -> bool;
+    }
+    // Without access screencapture still succeeds, but with only the desktop
+    if !unsafe { CGPreflightScreenCaptureAccess() } {
+        return Err(ContextError::PermissionDenied(
+            "Screen Recording access hasn't been granted".to_string(),
+        ));
+    }
+
+    let tasks = &state.context_tasks;
+    let focused = focused_app(tasks)?;
+    let script_path = get_script_path("get_window_id.js");
+    let window_id = run_script_with_args(
+        tasks,
+        "window_capture",
+        &script_path,
+        &[&focused.pid.to_string()],
+    )?;
+    if window_id.is_empty() {
+        return Ok(None);
+    }
+
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!(
+        "window-{}.png",
+        chrono::Local::now().format("%Y%m%d-%H%M%S-%3f")
+    ));
+    let output = tasks.output(
+        "window_capture",
+        Command::new("/usr/sbin/screencapture")
+            .args(["-x", "-o", "-l", &window_id])
+            .arg(&path),
+    )?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(ContextError::ScriptFailed(message));
+    }
+
+    // sips -Z scales up as well as down, so it only runs on large captures
+    let output = tasks.output(
+        "window_capture",
+        Command::new("/usr/bin/sips")
+            .args(["-g", "pixelWidth", "-g", "pixelHeight"])
+            .arg(&path),
+    )?;
+    let largest = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split(':').nth(1)?.trim().parse::<u32>().ok())
+        .max()
+        .unwrap_or(0);
+    if largest > MAX_CAPTURE_DIMENSION {
+        tasks.output(
+            "window_capture",
+            Command::new("/usr/bin/sips")
+                .args(["-Z", &MAX_CAPTURE_DIMENSION.to_string()])
+                .arg(&path),
+        )?;
+    }
+
+    Ok(Some(path))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn capture_window(_state: &AppState, _dir: &Path) -> Result<Option<PathBuf>, ContextError> {
+    Err(ContextError::Unsupported)
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct SystemVolume {
+    output_volume: u8,
+    muted: bool,
+    input_volume: u8,
+}
+
+#[cfg(target_os = "macos")]
+#[tauri::command]
+pub fn get_system_volume(
+    state: tauri::State<AppState>,
+    force: Option<bool>,
+) -> Result<SystemVolume, ContextError> {
+    cached(&state, "system_volume", force, || {
+        let script_path = get_script_path("get_system_volume.applescript");
+        let output = run_script(&state.context_tasks, "system_volume", &script_path)?;
+        Ok(serde_json::from_str(&output)?)
+    })
+}
+
+#[cfg(not(target_os = "macos"))]
+#[tauri::command]
+pub fn get_system_volume() -> Result<SystemVolume, ContextError> {
+    Err(ContextError::Unsupported)
+}
+
+// Returns the best fix found within the timeout, or TimedOut when there was
+// none and no earlier location to fall back on
+#[tauri::command]
+pub fn get_location(
+    state: tauri::State<AppState>,
+    force: Option<bool>,
+    options: Option<LocationOptions>,
+) -> Result<LocationInfo, ContextError> {
+    let options = options.unwrap_or_default();
+    let max_age = Duration::from_secs(state.config.get_location_max_age_secs());
+    // Held through the read so concurrent callers share one CoreLocation run
+    let mut last = state.location_cache.last.lock().unwrap();
+    if let (false, Some((read_at, location))) = (force.unwrap_or(false), last.as_ref()) {
+        if read_at.elapsed() < max_age && options.accepts(location) {
+            return Ok(location.clone());
+        }
+    }
+
+    match location(&state, &options) {
+        Ok(location) => {
+            *last = Some((Instant::now(), location.clone()));
+            Ok(location)
+        }
+        // Falling back would hide that the user needs to act
+        Err(e @ ContextError::PermissionDenied(_)) => Err(e),
+        Err(e) => match last.as_ref() {
+            Some((_, location)) => {
+                tracing::warn!(error = %e, "location unavailable, using the last known one");
+                Ok(LocationInfo {
+                    stale: true,
+                    ..location.clone()
+                })
+            }
+            None => Err(e),
+        },
+    }
+}
+
+const CORE_LOCATION_CLI: &str = "/opt/homebrew/bin/CoreLocationCLI";
+
+fn location(state: &AppState, options: &LocationOptions) -> Result<LocationInfo, ContextError> {
+    let tasks = &state.context_tasks;
+    let timeout = options
+        .timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or_else(|| tasks.timeout_for("location"));
+
+    let Some(desired) = options.desired_accuracy else {
+        let output = tasks.output_within(
+            "location",
+            Command::new(CORE_LOCATION_CLI).arg("--json"),
+            timeout,
+        )?;
+        if !output.status.success() {
+            return Err(location_error(&output));
+        }
+        return parse_location(&String::from_utf8_lossy(&output.stdout));
+    };
+
+    // CoreLocation refines its fix over the first few seconds, so watch the
+    // updates and keep the most accurate one until it's good enough
+    let mut best: Option<LocationInfo> = None;
+    let result = tasks.stream_lines(
+        "location",
+        Command::new(CORE_LOCATION_CLI).args(["--watch", "--json"]),
+        timeout,
+        |line| {
+            let Ok(fix) = parse_location(line) else {
+                return false;
+            };
+            let accuracy = fix.accuracy_meters.unwrap_or(f64::INFINITY);
+            let improved = match &best {
+                Some(best) => accuracy < best.accuracy_meters.unwrap_or(f64::INFINITY),
+                None => true,
+            };
+            if improved {
+                best = Some(fix);
+            }
+            accuracy <= desired
+        },
+    );
+
+    if let Some(best) = best {
+        return Ok(best);
+    }
+    Err(location_error(&result?))
+}
+
+// CoreLocationCLI exits with an explanation when Location Services are off or
+// the app isn't allowed to use them
+fn location_error(output: &Output) -> ContextError {
+    let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+    let lowercase = message.to_lowercase();
+    if lowercase.contains("denied") || lowercase.contains("disabled") {
+        return ContextError::PermissionDenied(message);
+    }
+    ContextError::ScriptFailed(message)
+}
+
+fn parse_location(json: &str) -> Result<LocationInfo, ContextError> {
+    let mut location_info: LocationInfo = serde_json::from_str(json.trim())?;
+    location_info.accuracy_meters = location_info.h_accuracy.trim().parse().ok();
     Ok(location_info)
 }
+
+// The BSD name of the Wi-Fi interface, usually en0 but not on every Mac
+#[cfg(target_os = "macos")]
+fn wifi_device(tasks: &ContextTasks) -> Result<Option<String>, ContextError> {
+    let output = tasks.output(
+        "wifi_ssid",
+        Command::new("/usr/sbin/networksetup").arg("-listallhardwareports"),
+    )?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let mut lines = listing.lines();
+    while let Some(line) = lines.next() {
+        if matches!(
+            line.trim(),
+            "Hardware Port: Wi-Fi" | "Hardware Port: AirPort"
+        ) {
+            return Ok(lines
+                .next()
+                .and_then(|device| device.trim().strip_prefix("Device: "))
+                .map(str::to_owned));
+        }
+    }
+    Ok(None)
+}
+
+// The network name is a steadier hint of where the user is than coordinates.
+// None when there's no Wi-Fi interface, it's off, or it isn't joined to a
+// network.
+#[cfg(target_os = "macos")]
+#[tauri::command]
+pub fn get_wifi_ssid(
+    state: tauri::State<AppState>,
+    force: Option<bool>,
+) -> Result<Option<String>, ContextError> {
+    cached(&state, "wifi_ssid", force, || {
+        let tasks = &state.context_tasks;
+        let Some(device) = wifi_device(tasks)? else {
+            return Ok(None);
+        };
+        let output = tasks.output(
+            "wifi_ssid",
+            Command::new("/usr/sbin/networksetup").args(["-getairportnetwork", &device]),
+        )?;
+        // Anything else is "You are not associated with an AirPort network."
+        // or an error about the interface being off
+        let ssid = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .strip_prefix("Current Wi-Fi Network: ")
+            .map(str::to_owned);
+        Ok(ssid)
+    })
+}
+
+#[cfg(not(target_os = "macos"))]
+#[tauri::command]
+pub fn get_wifi_ssid() -> Result<Option<String>, ContextError> {
+    Err(ContextError::Unsupported)
+}
+
+// Plain text on the clipboard, cut to THOUGHTS_CLIPBOARD_MAX_CHARS. None when
+// it's empty or holds something else, like an image or files.
+#[tauri::command]
+pub fn get_clipboard_text(
+    state: tauri::State<AppState>,
+    force: Option<bool>,
+) -> Result<Option<String>, ContextError> {
+    let max_chars = state.config.get_clipboard_max_chars();
+    cached(&state, "clipboard", force, || {
+        let text = clipboard_text(&state.context_tasks)?;
+        Ok(text.map(|text| text.chars().take(max_chars).collect()))
+    })
+}
+
+// `clipboard info` lists the pasteboard's types, e.g.
+// `{«class furl», 35}, {«class utf8», 12}`. Files copied in Finder come with a
+// file URL, and their names as text alongside it.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn holds_file_urls(clipboard_info: &str) -> bool {
+    clipboard_info.contains("«class furl»")
+}
+
+#[cfg(target_os = "macos")]
+fn clipboard_text(tasks: &ContextTasks) -> Result<Option<String>, ContextError> {
+    // pbpaste would return the copied files' names as if they were text
+    let info = tasks.output(
+        "clipboard",
+        Command::new("osascript").args(["-e", "clipboard info"]),
+    )?;
+    if info.status.success() && holds_file_urls(&String::from_utf8_lossy(&info.stdout)) {
+        return Ok(None);
+    }
+
+    let output = tasks.output(
+        "clipboard",
+        Command::new("/usr/bin/pbpaste").args(["-Prefer", "txt"]),
+    )?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(ContextError::ScriptFailed(message));
+    }
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(text.into_owned()))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn clipboard_text(_tasks: &ContextTasks) -> Result<Option<String>, ContextError> {
+    Err(ContextError::Unsupported)
+}
+
+// A context source that can be asked for by name. Adding one to PROVIDERS
+// makes it available through get_context_by_name and the snapshot, with no
+// command of its own.
+pub trait ContextProvider: Sync {
+    fn name(&self) -> &'static str;
+
+    fn capture(
+        &self,
+        app: &tauri::AppHandle,
+        force: Option<bool>,
+    ) -> Result<serde_json::Value, ContextError>;
+
+    // Whether get_context_snapshot gathers this source
+    fn in_snapshot(&self) -> bool {
+        true
+    }
+}
+
+fn app_state(app: &tauri::AppHandle) -> tauri::State<'_, AppState> {
+    use tauri::Manager;
+    app.state()
+}
+
+fn to_value<T: serde::Serialize>(
+    result: Result<T, ContextError>,
+) -> Result<serde_json::Value, ContextError> {
+    Ok(serde_json::to_value(result?)?)
+}
+
+struct FocusedAppProvider;
+
+impl ContextProvider for FocusedAppProvider {
+    fn name(&self) -> &'static str {
+        "focused_app"
+    }
+
+    fn capture(
+        &self,
+        app: &tauri::AppHandle,
+        force: Option<bool>,
+    ) -> Result<serde_json::Value, ContextError> {
+        to_value(get_focused_app(app_state(app), force))
+    }
+}
+
+struct BrowserUrlProvider;
+
+impl ContextProvider for BrowserUrlProvider {
+    fn name(&self) -> &'static str {
+        "browser_url"
+    }
+
+    // Goes through the cached focused app, so a snapshot usually reads the
+    // frontmost app once for both sources
+    fn capture(
+        &self,
+        app: &tauri::AppHandle,
+        force: Option<bool>,
+    ) -> Result<serde_json::Value, ContextError> {
+        let state = app_state(app);
+        let tasks = &state.context_tasks;
+        to_value(cached(&state, "browser_url", force, || {
+            let focused = cached(&state, "focused_app", force, || focused_app(tasks))?;
+            browser_url(tasks, &focused)
+        }))
+    }
+}
+
+struct ArcUrlProvider;
+
+impl ContextProvider for ArcUrlProvider {
+    fn name(&self) -> &'static str {
+        "arc_url"
+    }
+
+    fn capture(
+        &self,
+        app: &tauri::AppHandle,
+        force: Option<bool>,
+    ) -> Result<serde_json::Value, ContextError> {
+        to_value(active_arc_url(app_state(app), force))
+    }
+
+    // browser_url already covers Arc when it's frontmost
+    fn in_snapshot(&self) -> bool {
+        false
+    }
+}
+
+struct SpotifyProvider;
+
+impl ContextProvider for SpotifyProvider {
+    fn name(&self) -> &'static str {
+        "spotify"
+    }
+
+    fn capture(
+        &self,
+        app: &tauri::AppHandle,
+        force: Option<bool>,
+    ) -> Result<serde_json::Value, ContextError> {
+        to_value(get_spotify_track(app_state(app), force))
+    }
+}
+
+struct LocationProvider;
+
+impl ContextProvider for LocationProvider {
+    fn name(&self) -> &'static str {
+        "location"
+    }
+
+    fn capture(
+        &self,
+        app: &tauri::AppHandle,
+        force: Option<bool>,
+    ) -> Result<serde_json::Value, ContextError> {
+        to_value(get_location(app_state(app), force, None))
+    }
+}
+
+struct ClipboardProvider;
+
+impl ContextProvider for ClipboardProvider {
+    fn name(&self) -> &'static str {
+        "clipboard"
+    }
+
+    fn capture(
+        &self,
+        app: &tauri::AppHandle,
+        force: Option<bool>,
+    ) -> Result<serde_json::Value, ContextError> {
+        to_value(get_clipboard_text(app_state(app), force))
+    }
+}
+
+struct TerminalCommandProvider;
+
+impl ContextProvider for TerminalCommandProvider {
+    fn name(&self) -> &'static str {
+        "terminal_command"
+    }
+
+    fn capture(
+        &self,
+        app: &tauri::AppHandle,
+        _force: Option<bool>,
+    ) -> Result<serde_json::Value, ContextError> {
+        to_value(get_last_terminal_command(app_state(app)))
+    }
+}
+
+static PROVIDERS: &[&dyn ContextProvider] = &[
+    &FocusedAppProvider,
+    &BrowserUrlProvider,
+    &ArcUrlProvider,
+    &SpotifyProvider,
+    &LocationProvider,
+    &ClipboardProvider,
+    &TerminalCommandProvider,
+];
+
+// Whether `name` is a registered source. ContextSourceMap uses the same names.
+pub fn is_context_source(name: &str) -> bool {
+    PROVIDERS.iter().any(|provider| provider.name() == name)
+}
+
+// Any registered source by name, e.g. "spotify", in the same shape as its
+// dedicated command
+#[tauri::command]
+pub fn get_context_by_name(
+    app: tauri::AppHandle,
+    name: String,
+    force: Option<bool>,
+) -> Result<serde_json::Value, ContextError> {
+    let provider = PROVIDERS
+        .iter()
+        .find(|provider| provider.name() == name)
+        .ok_or(ContextError::UnknownSource(name))?;
+    provider.capture(&app, force)
+}
+
+// Everything the panel shows as context, gathered in one call and keyed by
+// source name. Only the sources configured for the frontmost app are
+// gathered, plus the focused app itself. They run in parallel; one that fails
+// or doesn't apply is null instead of failing the snapshot.
+#[tauri::command]
+pub async fn get_context_snapshot(
+    app: tauri::AppHandle,
+    force: Option<bool>,
+) -> Result<serde_json::Map<String, serde_json::Value>, ContextError> {
+    let sources = {
+        let app = app.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let state = app_state(&app);
+            let bundle_id = get_focused_app(state.clone(), force)
+                .map(|focused| focused.bundle_id)
+                .unwrap_or_default();
+            state.context_sources.sources_for(&bundle_id)
+        })
+        .await?
+    };
+
+    let captures: Vec<_> = PROVIDERS
+        .iter()
+        .filter(|provider| provider.in_snapshot())
+        .filter(|provider| {
+            provider.name() == FocusedAppProvider.name()
+                || sources.iter().any(|source| source == provider.name())
+        })
+        .map(|&provider| {
+            let app = app.clone();
+            let capture = tauri::async_runtime::spawn_blocking(move || {
+                provider
+                    .capture(&app, force)
+                    .unwrap_or(serde_json::Value::Null)
+            });
+            (provider.name(), capture)
+        })
+        .collect();
+
+    let mut snapshot = serde_json::Map::new();
+    for (name, capture) in captures {
+        snapshot.insert(name.to_owned(), capture.await?);
+    }
+    Ok(snapshot)
+}
+
+// Overrides the timeout for one context source until the app restarts.
+// Passing 0 clears the override so the source falls back to the default.
+#[tauri::command]
+pub fn set_source_timeout(state: tauri::State<AppState>, source: String, ms: u64) {
+    let mut timeouts = state.context_tasks.source_timeouts.lock().unwrap();
+    if ms == 0 {
+        timeouts.remove(&source);
+    } else {
+        timeouts.insert(source, ms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_javascript_from_apple_events_being_off() {
+        assert!(javascript_disabled(
+            "execution error: Error: You must enable 'Allow JavaScript from Apple Events' in the Developer section of Safari Settings to use 'do JavaScript'. (8)"
+        ));
+        assert!(javascript_disabled(
+            "execution error: Error: Executing JavaScript through AppleScript is turned off. (12)"
+        ));
+        assert!(!javascript_disabled(
+            "execution error: Not authorized to send Apple events to Safari. (-1743)"
+        ));
+    }
+
+    #[test]
+    fn parses_spotify_track() {
+        let track = parse_spotify_track("Bowie\nHeroes\ntrue\n61000\n371000").unwrap();
+        assert_eq!(track.artist, "Bowie");
+        assert_eq!(track.track, "Heroes");
+        assert!(track.is_playing);
+        assert_eq!(track.position_seconds, Some(61.0));
+        assert_eq!(track.duration_seconds, Some(371.0));
+    }
+
+    #[test]
+    fn parses_spotify_track_with_empty_artist() {
+        let output = script_output(b"\nEpisode 12\ntrue\n5000\n1800000\n");
+        let track = parse_spotify_track(&output).unwrap();
+        assert_eq!(track.artist, "");
+        assert_eq!(track.track, "Episode 12");
+        assert!(track.is_playing);
+        assert_eq!(track.position_seconds, Some(5.0));
+        assert_eq!(track.duration_seconds, Some(1800.0));
+    }
+
+    #[test]
+    fn parses_spotify_with_nothing_loaded() {
+        // The script's empty position and duration lines are trimmed away
+        let track = parse_spotify_track("Not playing\nNot playing\nfalse").unwrap();
+        assert!(!track.is_playing);
+        assert_eq!(track.position_seconds, None);
+        assert!(parse_spotify_track("Not playing").is_err());
+    }
+
+    #[test]
+    fn parses_mail_message() {
+        let message = parse_mail_message("Lunch?\nAda <ada@example.com>").unwrap();
+        assert_eq!(message.subject, "Lunch?");
+        assert_eq!(message.from.as_deref(), Some("Ada <ada@example.com>"));
+    }
+
+    #[test]
+    fn parses_mail_message_with_empty_subject_or_sender() {
+        let message = parse_mail_message("\nAda <ada@example.com>").unwrap();
+        assert_eq!(message.subject, "");
+        assert_eq!(message.from.as_deref(), Some("Ada <ada@example.com>"));
+
+        // The script's trailing empty line is trimmed away
+        let message = parse_mail_message("Lunch?").unwrap();
+        assert_eq!(message.subject, "Lunch?");
+        assert_eq!(message.from, None);
+
+        assert!(parse_mail_message("").is_none());
+    }
+
+    #[test]
+    fn parses_playback_queue_with_quoted_names() {
+        let queue = parse_playback_queue(
+            r#"{"source": "apple_music", "collection": "\"Heroes\"", "upcoming": [{"artist": "AC\\DC", "track": "Say \"Hi\""}]}"#,
+        )
+        .unwrap();
+        assert_eq!(queue.collection.as_deref(), Some(r#""Heroes""#));
+        assert_eq!(queue.upcoming.len(), 1);
+        assert_eq!(queue.upcoming[0].artist, r"AC\DC");
+        assert_eq!(queue.upcoming[0].track, r#"Say "Hi""#);
+    }
+
+    #[test]
+    fn parses_ide_status_with_quoted_scheme() {
+        let status = parse_ide_status(
+            r#"{"app": "Xcode", "scheme": "My \"App\" \\ iOS", "building": true, "last_result": null}"#,
+        )
+        .unwrap();
+        assert_eq!(status.scheme.as_deref(), Some(r#"My "App" \ iOS"#));
+        assert!(status.building);
+        assert_eq!(status.last_result, None);
+    }
+
+    #[test]
+    fn recognizes_copied_files() {
+        assert!(holds_file_urls(
+            "«class furl», 35, «class utf8», 12, «class ut16», 26, string, 12"
+        ));
+        assert!(!holds_file_urls(
+            "«class utf8», 11, «class ut16», 24, string, 11"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn drain_kills_helpers_that_outlive_the_timeout() {
+        let tasks = ContextTasks::new(1000, HashMap::new());
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        tasks.children.lock().unwrap().insert(child.id());
+
+        tasks.drain(Duration::from_millis(50));
+
+        let status = child.wait().unwrap();
+        assert!(!status.success());
+        assert!(tasks.children.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::config::write_atomic;
+use crate::context::{is_browser, is_context_source, TERMINAL_BUNDLE_IDS};
+use crate::AppState;
+
+// Used for any app without an entry of its own or for its category
+const DEFAULT_KEY: &str = "default";
+
+fn category(bundle_id: &str) -> Option<&'static str> {
+    if is_browser(bundle_id) {
+        Some("browser")
+    } else if TERMINAL_BUNDLE_IDS.contains(&bundle_id) {
+        Some("terminal")
+    } else {
+        None
+    }
+}
+
+fn default_mapping() -> HashMap<String, Vec<String>> {
+    let entries: [(&str, &[&str]); 3] = [
+        (DEFAULT_KEY, &["spotify", "location"]),
+        ("browser", &["browser_url", "spotify", "location"]),
+        ("terminal", &["terminal_command", "spotify", "location"]),
+    ];
+    entries
+        .into_iter()
+        .map(|(key, sources)| {
+            let sources = sources.iter().map(|s| s.to_string()).collect();
+            (key.to_string(), sources)
+        })
+        .collect()
+}
+
+// The first source in `sources` that isn't a registered context provider
+fn unknown_source<'a>(sources: impl IntoIterator<Item = &'a String>) -> Option<&'a String> {
+    sources
+        .into_iter()
+        .find(|source| !is_context_source(source))
+}
+
+fn parse_mapping(content: &str) -> Result<HashMap<String, Vec<String>>, String> {
+    let entries: HashMap<String, Vec<String>> =
+        serde_json::from_str(content).map_err(|e| e.to_string())?;
+    match unknown_source(entries.values().flatten()) {
+        Some(unknown) => Err(format!("Unknown context source {unknown:?}")),
+        None => Ok(entries),
+    }
+}
+
+// Which context sources to gather for the frontmost app, keyed by bundle id or
+// by category ("browser", "terminal"). Sources are named as in get_context_by_name
+// (e.g. "browser_url"). Lookups try the bundle id, then its category, then
+// "default". Persisted as JSON in the config dir once edited.
+pub struct ContextSourceMap {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl ContextSourceMap {
+    // A file that doesn't parse or names an unknown source is ignored in
+    // favour of the defaults, rather than silently gathering nothing for it
+    pub fn load(path: PathBuf) -> Self {
+        let entries = match fs::read_to_string(&path) {
+            Ok(content) => parse_mapping(&content).unwrap_or_else(|e| {
+                log_error!("Ignoring {}: {e}", path.display());
+                default_mapping()
+            }),
+            Err(_) => default_mapping(),
+        };
+        ContextSourceMap {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn save(&self, entries: &HashMap<String, Vec<String>>) -> io::Result<()> {
+        write_atomic(&self.path, &serde_json::to_string_pretty(entries)?)
+    }
+
+    pub fn sources_for(&self, bundle_id: &str) -> Vec<String> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(bundle_id)
+            .or_else(|| category(bundle_id).and_then(|category| entries.get(category)))
+            .or_else(|| entries.get(DEFAULT_KEY))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[tauri::command]
+pub fn get_context_source_map(state: tauri::State<AppState>) -> HashMap<String, Vec<String>> {
+    state.context_sources.entries.lock().unwrap().clone()
+}
+
+// Sets the sources for a bundle id, category or "default". Passing None
+// removes the entry so the app falls back to its category or the default.
+#[tauri::command]
+pub fn set_context_sources(
+    state: tauri::State<AppState>,
+    key: String,
+    sources: Option<Vec<String>>,
+) -> Result<(), String> {
+    if let Some(unknown) = unknown_source(sources.iter().flatten()) {
+        return Err(format!("Unknown context source {unknown:?}"));
+    }
+
+    let mut entries = state.context_sources.entries.lock().unwrap();
+    let previous = match sources {
+        Some(sources) => entries.insert(key.clone(), sources),
+        None => entries.remove(&key),
+    };
+    if let Err(e) = state.context_sources.save(&entries) {
+        match previous {
+            Some(previous) => entries.insert(key, previous),
+            None => entries.remove(&key),
+        };
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_context_sources_for_app(
+    state: tauri::State<AppState>,
+    bundle_id: String,
+) -> Vec<String> {
+    state.context_sources.sources_for(&bundle_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_mapping_names_registered_sources() {
+        let defaults = default_mapping();
+        assert_eq!(unknown_source(defaults.values().flatten()), None);
+    }
+
+    #[test]
+    fn rejects_unknown_source_names() {
+        let error = parse_mapping(r#"{ "default": ["spotify", "url"] }"#).unwrap_err();
+        assert_eq!(error, r#"Unknown context source "url""#);
+    }
+
+    #[test]
+    fn falls_back_to_defaults_for_an_invalid_file() {
+        let dir = crate::test_support::TempDir::new();
+        let path = dir.path().join("context-sources.json");
+        fs::write(&path, r#"{ "default": ["weather"] }"#).unwrap();
+
+        let map = ContextSourceMap::load(path);
+
+        assert_eq!(map.sources_for("com.example.app"), ["spotify", "location"]);
+    }
+
+    #[test]
+    fn looks_up_bundle_id_then_category_then_default() {
+        let dir = crate::test_support::TempDir::new();
+        let path = dir.path().join("context-sources.json");
+        let content = r#"{
+            "com.apple.Safari": ["clipboard"],
+            "browser": ["browser_url"],
+            "default": ["location"]
+        }"#;
+        fs::write(&path, content).unwrap();
+
+        let map = ContextSourceMap::load(path);
+
+        assert_eq!(map.sources_for("com.apple.Safari"), ["clipboard"]);
+        assert_eq!(map.sources_for("com.google.Chrome"), ["browser_url"]);
+        assert_eq!(map.sources_for("com.example.app"), ["location"]);
+    }
+}
@@ -0,0 +1,143 @@
+// Lets automation tools drive a running instance from the command line, e.g.
+// `wazzup toggle` from a shell script or Raycast. The running app listens on a
+// Unix socket in the config dir; a second invocation with a subcommand sends it
+// a request and exits instead of starting another copy.
+//
+// Exit codes for subcommands:
+//   0  the running instance handled the request
+//   1  an instance is running but didn't handle the request
+//   2  unknown subcommand
+// When no instance is running, the app launches normally.
+
+use std::io;
+use std::path::Path;
+
+#[derive(Clone, Copy)]
+pub enum Subcommand {
+    Toggle,
+}
+
+impl Subcommand {
+    fn as_str(self) -> &'static str {
+        match self {
+            Subcommand::Toggle => "toggle",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "toggle" => Some(Subcommand::Toggle),
+            _ => None,
+        }
+    }
+}
+
+pub const EXIT_HANDLED: i32 = 0;
+pub const EXIT_FAILED: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+// Flags are left alone since the OS and dev tooling pass their own
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Subcommand>, String> {
+    while let Some(arg) = args.next() {
+        // The profile name isn't a subcommand
+        if arg == "--profile" {
+            args.next();
+            continue;
+        }
+        if arg.starts_with('-') {
+            continue;
+        }
+        return Subcommand::parse(&arg)
+            .map(Some)
+            .ok_or(format!("Unknown subcommand: {arg}"));
+    }
+    Ok(None)
+}
+
+// Returns Ok(false) when no instance is listening
+#[cfg(unix)]
+pub fn send(socket_path: &Path, command: Subcommand) -> io::Result<bool> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::time::Duration;
+
+    let mut stream = match UnixStream::connect(socket_path) {
+        Ok(stream) => stream,
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(false)
+        }
+        Err(e) => return Err(e),
+    };
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    writeln!(stream, "{}", command.as_str())?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    match reply.trim() {
+        "ok" => Ok(true),
+        other => Err(io::Error::other(format!("Unexpected reply: {other:?}"))),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn send(_socket_path: &Path, _command: Subcommand) -> io::Result<bool> {
+    Ok(false)
+}
+
+#[cfg(unix)]
+pub fn listen(app: tauri::AppHandle, socket_path: &Path) -> io::Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+    use std::time::Duration;
+    use tauri::Manager;
+
+    // A socket left behind by a crashed run would make bind fail
+    let _ = std::fs::remove_file(socket_path);
+    let listener = UnixListener::bind(socket_path)?;
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // Requests are read one at a time, so a client that connects and
+            // never sends a line would otherwise hold up every later one
+            if stream
+                .set_read_timeout(Some(Duration::from_secs(2)))
+                .is_err()
+            {
+                continue;
+            }
+            let mut line = String::new();
+            let Ok(mut reader) = stream.try_clone().map(BufReader::new) else {
+                continue;
+            };
+            if reader.read_line(&mut line).is_err() {
+                continue;
+            }
+
+            let reply = match Subcommand::parse(line.trim()) {
+                Some(Subcommand::Toggle) if app.get_webview_window("quick-panel").is_none() => {
+                    "error: running headless".to_string()
+                }
+                Some(Subcommand::Toggle) => {
+                    let handle = app.clone();
+                    match app.run_on_main_thread(move || crate::toggle_launchbar(&handle)) {
+                        Ok(()) => "ok".to_string(),
+                        Err(e) => format!("error: {e}"),
+                    }
+                }
+                None => "error: unknown command".to_string(),
+            };
+            let _ = writeln!(&stream, "{reply}");
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn listen(_app: tauri::AppHandle, _socket_path: &Path) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+// Plain-HTTP endpoint whose Date header is used as the reference clock
+const TIME_REFERENCE_HOST: &str = "captive.apple.com";
+const TIME_REFERENCE_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Skew beyond this is reported as a problem; HTTP dates only have one-second
+// resolution, so anything much tighter would be noise
+const CLOCK_SKEW_WARN_SECONDS: f64 = 5.0;
+
+#[derive(serde::Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ClockSync {
+    Ok { skew_seconds: f64 },
+    Skewed { skew_seconds: f64 },
+    // No network, or the reference didn't answer in time
+    Unknown,
+}
+
+fn fetch_reference_time() -> io::Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let addr = (TIME_REFERENCE_HOST, 80)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No address for time reference"))?;
+
+    let sent_at = Utc::now();
+    let mut stream = TcpStream::connect_timeout(&addr, TIME_REFERENCE_TIMEOUT)?;
+    stream.set_read_timeout(Some(TIME_REFERENCE_TIMEOUT))?;
+    stream.set_write_timeout(Some(TIME_REFERENCE_TIMEOUT))?;
+    write!(
+        stream,
+        "HEAD / HTTP/1.1\r\nHost: {TIME_REFERENCE_HOST}\r\nConnection: close\r\n\r\n"
+    )?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("date") {
+                let received_at = Utc::now();
+                let server_time = DateTime::parse_from_rfc2822(value.trim())
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                // Compare against the midpoint of the round trip
+                let local_time = sent_at + (received_at - sent_at) / 2;
+                return Ok((local_time, server_time.with_timezone(&Utc)));
+            }
+        }
+        line.clear();
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Time reference sent no Date header",
+    ))
+}
+
+// Positive skew means the local clock is ahead of the reference
+#[tauri::command]
+pub async fn check_clock_sync() -> ClockSync {
+    let result = tauri::async_runtime::spawn_blocking(fetch_reference_time).await;
+
+    match result {
+        Ok(Ok((local_time, server_time))) => {
+            let skew_seconds = (local_time - server_time).num_milliseconds() as f64 / 1000.0;
+            if skew_seconds.abs() > CLOCK_SKEW_WARN_SECONDS {
+                log_error!("Warning: system clock is off by {skew_seconds:.1}s");
+                ClockSync::Skewed { skew_seconds }
+            } else {
+                ClockSync::Ok { skew_seconds }
+            }
+        }
+        _ => ClockSync::Unknown,
+    }
+}
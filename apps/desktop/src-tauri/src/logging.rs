@@ -0,0 +1,95 @@
+// Log lines go to the terminal as before, and are optionally mirrored to the
+// unified system log on macOS so they can be filtered in Console.app under the
+// app's subsystem.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static OS_LOG_ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy)]
+pub enum Level {
+    Info,
+    Error,
+}
+
+pub fn set_os_log_enabled(enabled: bool) {
+    OS_LOG_ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn mirror(category: &str, level: Level, message: &str) {
+    if OS_LOG_ENABLED.load(Ordering::SeqCst) {
+        os_log::write(category, level, message);
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os_log {
+    use std::collections::HashMap;
+    use std::ffi::{c_char, c_void, CString};
+    use std::sync::Mutex;
+
+    use super::Level;
+
+    const SUBSYSTEM: &str = "com.abhi.thoughts";
+
+    // os_log_type_t values from <os/log.h>
+    const OS_LOG_TYPE_DEFAULT: u8 = 0x00;
+    const OS_LOG_TYPE_ERROR: u8 = 0x10;
+
+    extern "C" {
+        fn os_log_create(subsystem: *const c_char, category: *const c_char) -> *mut c_void;
+        // Defined in src/os_log.c; os_log itself is a macro with no symbol to link
+        fn thoughts_os_log(log: *mut c_void, log_type: u8, message: *const c_char);
+    }
+
+    // os_log_t handles live for the life of the process, so one per category
+    // is created lazily and kept. Stored as usize since raw pointers aren't Send.
+    static HANDLES: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+
+    fn handle(category: &str) -> Option<*mut c_void> {
+        let mut handles = HANDLES.lock().ok()?;
+        let handles = handles.get_or_insert_with(HashMap::new);
+        if let Some(&log) = handles.get(category) {
+            return Some(log as *mut c_void);
+        }
+
+        let subsystem = CString::new(SUBSYSTEM).ok()?;
+        let category_c = CString::new(category).ok()?;
+        let log = unsafe { os_log_create(subsystem.as_ptr(), category_c.as_ptr()) };
+        if log.is_null() {
+            return None;
+        }
+        handles.insert(category.to_owned(), log as usize);
+        Some(log)
+    }
+
+    pub fn write(category: &str, level: Level, message: &str) {
+        let Some(log) = handle(category) else {
+            return;
+        };
+        // Interior NULs would truncate the line anyway
+        let Ok(message) = CString::new(message.replace('\0', "")) else {
+            return;
+        };
+        let log_type = match level {
+            Level::Info => OS_LOG_TYPE_DEFAULT,
+            Level::Error => OS_LOG_TYPE_ERROR,
+        };
+        unsafe { thoughts_os_log(log, log_type, message.as_ptr()) };
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod os_log {
+    use super::Level;
+
+    pub fn write(_category: &str, _level: Level, _message: &str) {}
+}
+
+macro_rules! log_error {
+    ($($arg:tt)*) => {{
+        let message = format!($($arg)*);
+        eprintln!("{message}");
+        tracing::error!("{message}");
+        $crate::logging::mirror("app", $crate::logging::Level::Error, &message);
+    }};
+}
@@ -1,59 +1,401 @@
-use colored::Colorize;
-use std::{env, str::FromStr, sync::Mutex};
+use std::{
+    env,
+    str::FromStr,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 use tauri::{
-    image::Image,
-    menu::{Menu, MenuItem},
-    tray::TrayIconBuilder,
-    Manager, RunEvent, Url, WebviewUrl, WebviewWindowBuilder,
+    tray::TrayIconBuilder, Emitter, Manager, RunEvent, Url, WebviewUrl, WebviewWindowBuilder,
+};
+use tauri_plugin_global_shortcut::{Code, Modifiers, Shortcut};
+use tauri_plugin_shell::process::CommandChild;
+
+#[macro_use]
+mod logging;
+
+mod audio;
+use audio::{
+    get_ambient_level, list_input_devices, set_recording_input_device, InputDevicePreference,
+};
+
+mod autostart;
+use autostart::{get_launch_at_login, set_launch_at_login};
+
+mod capabilities;
+use capabilities::{check_permissions, get_capability_matrix, request_permissions};
+
+mod capture_log;
+use capture_log::{
+    get_context_at, record_context, set_next_capture_metadata, take_next_capture_metadata,
+    CaptureLog, NextCaptureMetadata,
 };
-use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
-use tauri_plugin_shell::process::{CommandChild, CommandEvent};
-use tauri_plugin_shell::ShellExt;
 
 mod config;
-use config::Config;
+use config::{Config, MainWindowDisplay, WindowGeometry};
+
+mod control;
 
 mod context;
-use context::{active_arc_url, get_focused_app, get_location, get_spotify_track};
+use context::{
+    active_arc_url, active_browser_url, capture_focused_window, get_accent_color,
+    get_active_window_bounds, get_clipboard_text, get_context_by_name, get_context_snapshot,
+    get_email_context, get_focused_app, get_git_diff_summary, get_ide_status, get_idle_seconds,
+    get_last_terminal_command, get_location, get_notes_app_context, get_now_playing,
+    get_page_scroll_context, get_playback_queue, get_recent_documents, get_spotify_track,
+    get_system_volume, get_wifi_ssid, set_source_timeout, set_window_bounds_tracking, ContextCache,
+    ContextTasks, LocationCache, RecentDocument, TtlCache,
+};
+
+mod context_sources;
+use context_sources::{
+    get_context_source_map, get_context_sources_for_app, set_context_sources, ContextSourceMap,
+};
+
+mod diagnostics;
+use diagnostics::check_clock_sync;
+
+mod metrics;
+use metrics::{get_metrics, Metrics};
+
+mod panel;
+use panel::{set_hide_on_blur, set_panel_auto_resize, set_quickpanel_size};
+
+#[cfg(target_os = "macos")]
+mod power;
+
+mod recovery;
+use recovery::{recover_state, resume_record_session, StateSnapshot};
+
+mod relocate;
+use relocate::{get_config_dir, migrate_config_dir, open_config_dir};
+
+mod schedule;
+use schedule::{add_schedule, list_schedules, remove_schedule, Schedules};
+
+mod server;
+use server::{
+    is_server_alive, list_server_builds, reconcile_server_state, restart_server, spawn_server,
+    switch_server_build,
+};
+
+mod shortcuts;
+use shortcuts::{
+    diagnose_shortcut_owner, is_shortcut_registered, register_shortcuts, set_shortcut,
+    try_register_shortcut, ShortcutAction, ShortcutBindings,
+};
+
+mod storage;
+use storage::{cleanup_old_artifacts, get_storage_usage};
+
+mod streak;
+use streak::{get_capture_streak, CaptureStreak};
+
+mod trace;
+use trace::{get_log_path, set_trace_level};
+
+mod tray;
+use tray::{set_tray_badge, TrayMenu, TRAY_ID};
+
+mod updates;
+use updates::{check_for_update, get_app_version};
+
+#[cfg(test)]
+mod test_support;
 
 // Record mode state
 struct RecordModeState {
     enabled: bool,
+    // Frontend-generated id of the edit history being recorded
+    session_id: Option<i64>,
 }
 
 // State type to hold our child process and config
 struct AppState {
     server: Mutex<Option<CommandChild>>,
+    // Name of the alternative server build in use, None for the bundled sidecar
+    server_build: Mutex<Option<String>>,
     config: Config,
     record_mode: Mutex<RecordModeState>,
+    capture_log: CaptureLog,
+    tray_menu: TrayMenu,
+    privacy_blur: AtomicBool,
+    install_id: Mutex<Option<String>>,
+    context_tasks: ContextTasks,
+    recovered_state: Mutex<Option<StateSnapshot>>,
+    recent_documents: TtlCache<Vec<RecentDocument>>,
+    context_cache: ContextCache,
+    location_cache: LocationCache,
+    shortcuts: ShortcutBindings,
+    schedules: Schedules,
+    next_capture_metadata: NextCaptureMetadata,
+    metrics: Metrics,
+    panel_auto_resize: AtomicBool,
+    hide_on_blur: AtomicBool,
+    // When do not disturb last held the panel back, see panel::suppressed
+    panel_suppressed_at: Mutex<Option<Instant>>,
+    capture_streak: CaptureStreak,
+    input_device: InputDevicePreference,
+    // Generation of the active-window tracker, see set_window_bounds_tracking
+    window_tracking: AtomicU64,
+    context_sources: ContextSourceMap,
+}
+
+// How long in-flight context helpers get to finish before being killed on quit
+const CONTEXT_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+const RECENT_DOCUMENTS_TTL: Duration = Duration::from_secs(30);
+
+// Title bar styles only exist on macOS; elsewhere the window keeps the
+// platform's standard decorations whatever the setting says
+#[cfg(target_os = "macos")]
+fn main_window_title_bar_style(app: &tauri::AppHandle) -> tauri::TitleBarStyle {
+    use config::TitleBarStyle;
+
+    let style = app
+        .try_state::<AppState>()
+        .map(|state| state.config.get_main_window_title_bar_style())
+        .unwrap_or_default();
+
+    match style {
+        TitleBarStyle::Visible => tauri::TitleBarStyle::Visible,
+        TitleBarStyle::Overlay => tauri::TitleBarStyle::Overlay,
+        TitleBarStyle::Transparent => tauri::TitleBarStyle::Transparent,
+    }
+}
+
+const MAIN_WINDOW_WIDTH: f64 = 800.0;
+const MAIN_WINDOW_HEIGHT: f64 = 600.0;
+
+// The display picked by the `main_window_display` setting, or None to use the
+// primary one
+fn main_window_monitor(app: &tauri::AppHandle) -> Option<tauri::Monitor> {
+    let state = app.try_state::<AppState>()?;
+    match state.config.get_main_window_display() {
+        MainWindowDisplay::Primary => None,
+        MainWindowDisplay::Index(index) => app.available_monitors().ok()?.into_iter().nth(index),
+        MainWindowDisplay::Cursor => {
+            let cursor = app.cursor_position().ok()?;
+            app.monitor_from_point(cursor.x, cursor.y).ok()?
+        }
+        MainWindowDisplay::ActiveWindow => {
+            // Window bounds come back in points, so compare in logical units
+            let bounds = context::front_window_bounds(&state.context_tasks).ok()??;
+            let center_x = bounds.x + bounds.width / 2.0;
+            let center_y = bounds.y + bounds.height / 2.0;
+            app.available_monitors().ok()?.into_iter().find(|monitor| {
+                let scale = monitor.scale_factor();
+                let position = monitor.position().to_logical::<f64>(scale);
+                let size = monitor.size().to_logical::<f64>(scale);
+                (position.x..position.x + size.width).contains(&center_x)
+                    && (position.y..position.y + size.height).contains(&center_y)
+            })
+        }
+    }
+}
+
+// The saved geometry fitted onto the display it was left on. None when that
+// display is gone, so the window opens at the default spot instead.
+fn restored_main_window_geometry(app: &tauri::AppHandle) -> Option<WindowGeometry> {
+    let state = app.try_state::<AppState>()?;
+    let saved = state.config.read_window_geometry()?;
+    let center_x = saved.x + saved.width / 2.0;
+    let center_y = saved.y + saved.height / 2.0;
+
+    app.available_monitors()
+        .ok()?
+        .into_iter()
+        .find_map(|monitor| {
+            let scale = monitor.scale_factor();
+            let position = monitor.position().to_logical::<f64>(scale);
+            let size = monitor.size().to_logical::<f64>(scale);
+            let on_monitor = (position.x..position.x + size.width).contains(&center_x)
+                && (position.y..position.y + size.height).contains(&center_y);
+            if !on_monitor {
+                return None;
+            }
+
+            let width = saved.width.min(size.width);
+            let height = saved.height.min(size.height);
+            Some(WindowGeometry {
+                x: saved.x.clamp(position.x, position.x + size.width - width),
+                y: saved.y.clamp(position.y, position.y + size.height - height),
+                width,
+                height,
+            })
+        })
+}
+
+// Moves and resizes arrive in bursts while dragging, so only the last one
+// within this window is written
+const GEOMETRY_SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+static GEOMETRY_SAVES: AtomicU64 = AtomicU64::new(0);
+
+fn save_main_window_geometry(window: &tauri::WebviewWindow) {
+    let generation = GEOMETRY_SAVES.fetch_add(1, Ordering::SeqCst) + 1;
+    let window = window.clone();
+
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(GEOMETRY_SAVE_DEBOUNCE).await;
+        if GEOMETRY_SAVES.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        // A maximized or minimized frame isn't a layout worth restoring
+        if window.is_maximized().unwrap_or(false) || window.is_minimized().unwrap_or(false) {
+            return;
+        }
+        let (Ok(scale), Ok(position), Ok(size)) = (
+            window.scale_factor(),
+            window.outer_position(),
+            window.inner_size(),
+        ) else {
+            return;
+        };
+        let position = position.to_logical::<f64>(scale);
+        let size = size.to_logical::<f64>(scale);
+        let geometry = WindowGeometry {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+        };
+
+        if let Some(state) = window.try_state::<AppState>() {
+            if let Err(e) = state.config.write_window_geometry(&geometry) {
+                log_error!("Failed to save main window geometry: {e}");
+            }
+        }
+    });
 }
 
-fn create_main_window(app: &tauri::AppHandle) {
+fn create_main_window(app: &tauri::AppHandle) -> tauri::Result<()> {
+    let restored = restored_main_window_geometry(app);
+    let (width, height) = restored
+        .map(|geometry| (geometry.width, geometry.height))
+        .unwrap_or((MAIN_WINDOW_WIDTH, MAIN_WINDOW_HEIGHT));
     let win_builder =
         WebviewWindowBuilder::new(app, "main", WebviewUrl::App("/main-window".into()))
             .title("Thoughts")
-            .inner_size(800.0, 600.0)
+            .inner_size(width, height)
             .resizable(true)
             .maximizable(true)
             .minimizable(true)
             .closable(true)
-            .transparent(true)
-            .center()
-            .title_bar_style(tauri::TitleBarStyle::Overlay)
-            .build()
-            .unwrap();
+            .transparent(true);
+    let win_builder = match (restored, main_window_monitor(app)) {
+        (Some(geometry), _) => win_builder.position(geometry.x, geometry.y),
+        (None, Some(monitor)) => {
+            let scale = monitor.scale_factor();
+            let position = monitor.position().to_logical::<f64>(scale);
+            let size = monitor.size().to_logical::<f64>(scale);
+            win_builder.position(
+                position.x + (size.width - MAIN_WINDOW_WIDTH) / 2.0,
+                position.y + (size.height - MAIN_WINDOW_HEIGHT) / 2.0,
+            )
+        }
+        (None, None) => win_builder.center(),
+    };
+    #[cfg(target_os = "macos")]
+    let win_builder = win_builder.title_bar_style(main_window_title_bar_style(app));
+    let win_builder = win_builder.build()?;
+
+    let window = win_builder.clone();
+    win_builder.on_window_event(move |event| {
+        if let tauri::WindowEvent::Moved(_) | tauri::WindowEvent::Resized(_) = event {
+            save_main_window_geometry(&window);
+        }
+    });
+
+    // Obscure the window as soon as it loses focus if the user opted in
+    if let Some(state) = app.try_state::<AppState>() {
+        if state.config.privacy_blur_on_focus_loss() {
+            let app_handle = app.clone();
+            win_builder.on_window_event(move |event| {
+                if let tauri::WindowEvent::Focused(false) = event {
+                    apply_privacy_blur(&app_handle, true);
+                }
+            });
+        }
+    }
 
     let _ = win_builder.show();
     let _ = win_builder.set_focus();
+    Ok(())
+}
+
+fn apply_privacy_blur(app: &tauri::AppHandle, enabled: bool) {
+    if let Some(state) = app.try_state::<AppState>() {
+        state.privacy_blur.store(enabled, Ordering::SeqCst);
+    }
+    // The main window renders the overlay itself in response to this event
+    let _ = app.emit_to("main", "privacy-blur", enabled);
 }
 
+// Brings the main window forward as it was left, and only creates it when it
+// isn't open. An existing window gets `main-window-focused` so it can refresh
+// its data without losing anything being edited.
 #[tauri::command]
-fn open_main_window(app: tauri::AppHandle) {
+fn focus_main_window(app: tauri::AppHandle) -> Result<(), String> {
+    let Some(window) = app.get_webview_window("main") else {
+        return create_main_window(&app).map_err(|e| e.to_string());
+    };
+    if window.is_minimized().unwrap_or(false) {
+        window.unminimize().map_err(|e| e.to_string())?;
+    }
+    window.show().map_err(|e| e.to_string())?;
+    window.set_focus().map_err(|e| e.to_string())?;
+    let _ = window.emit("main-window-focused", ());
+    Ok(())
+}
+
+#[tauri::command]
+fn open_main_window(app: tauri::AppHandle) -> Result<(), String> {
     // Always destroy existing window and create fresh one to avoid stale data
     if let Some(window) = app.get_webview_window("main") {
         let _ = window.close();
     }
-    create_main_window(&app);
+    create_main_window(&app).map_err(|e| e.to_string())
+}
+
+// Regular puts the app in the Dock and the Cmd-Tab switcher; Accessory keeps
+// it to the menu bar
+#[cfg(target_os = "macos")]
+fn apply_activation_policy(app: &tauri::AppHandle, show_in_dock: bool) -> tauri::Result<()> {
+    let policy = if show_in_dock {
+        tauri::ActivationPolicy::Regular
+    } else {
+        tauri::ActivationPolicy::Accessory
+    };
+    app.set_activation_policy(policy)
+}
+
+// There's no Dock elsewhere, so there's nothing to apply
+#[cfg(not(target_os = "macos"))]
+fn apply_activation_policy(_app: &tauri::AppHandle, _show_in_dock: bool) -> tauri::Result<()> {
+    Ok(())
+}
+
+// Lasts until the app restarts; show_in_dock in config.toml sets it for good
+#[cfg(target_os = "macos")]
+#[tauri::command]
+fn set_show_in_dock(app: tauri::AppHandle, show: bool) -> Result<(), String> {
+    apply_activation_policy(&app, show).map_err(|e| e.to_string())?;
+    // An accessory app's windows lose focus when it becomes a regular one,
+    // so the main window is brought back to the front
+    if show {
+        if let Some(window) = app.get_webview_window("main") {
+            window.set_focus().map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+#[tauri::command]
+fn set_show_in_dock(show: bool) -> Result<(), String> {
+    let _ = show;
+    Err("The Dock is only available on macOS".to_string())
 }
 
 #[tauri::command]
@@ -85,15 +427,91 @@ fn open_replay_window(app: tauri::AppHandle, thought_id: i64) -> Result<(), Stri
 #[tauri::command]
 fn close_quickpanel(app: tauri::AppHandle) {
     if let Some(window) = app.get_webview_window("quick-panel") {
-        window.hide().unwrap();
+        if let Err(e) = window.hide() {
+            log_error!("Failed to hide the quick panel: {e}");
+        }
     }
 }
 
+#[derive(Clone, serde::Serialize)]
+struct PanelToast {
+    message: String,
+    duration_ms: u64,
+}
+
 #[tauri::command]
-fn toggle_record_mode(state: tauri::State<AppState>) -> Result<bool, String> {
-    let mut record_mode = state.record_mode.lock().unwrap();
-    record_mode.enabled = !record_mode.enabled;
-    Ok(record_mode.enabled)
+fn show_panel_toast(
+    app: tauri::AppHandle,
+    message: String,
+    duration_ms: u64,
+    reveal: Option<bool>,
+) -> Result<(), String> {
+    let window = app
+        .get_webview_window("quick-panel")
+        .ok_or("Quick panel window not found")?;
+
+    // Optionally flash a hidden panel without stealing focus, hiding it again
+    // once the toast has run its course
+    let revealed = !window.is_visible().unwrap_or(false) && reveal.unwrap_or(false);
+    if revealed {
+        window.show().map_err(|e| e.to_string())?;
+    }
+
+    window
+        .emit(
+            "panel-toast",
+            PanelToast {
+                message,
+                duration_ms,
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    if revealed {
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(duration_ms)).await;
+            // Leave it up if the user started interacting with it meanwhile
+            if !window.is_focused().unwrap_or(false) {
+                let _ = window.hide();
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, serde::Serialize)]
+struct RecordModeChange {
+    enabled: bool,
+    session_id: Option<i64>,
+}
+
+// Flips record mode wherever it's toggled from, and keeps the tray and every
+// window in step through `record-mode-changed`. A session started without an
+// id from the panel gets one in the panel's temporary format.
+fn flip_record_mode(app: &tauri::AppHandle, session_id: Option<i64>) -> bool {
+    let state = app.state::<AppState>();
+    let change = {
+        let mut record_mode = state.record_mode.lock().unwrap();
+        record_mode.enabled = !record_mode.enabled;
+        record_mode.session_id = record_mode
+            .enabled
+            .then(|| session_id.unwrap_or_else(|| -chrono::Utc::now().timestamp_millis()));
+        RecordModeChange {
+            enabled: record_mode.enabled,
+            session_id: record_mode.session_id,
+        }
+    };
+    state
+        .tray_menu
+        .update(app, |tray_state| tray_state.record_mode = change.enabled);
+    let _ = app.emit("record-mode-changed", &change);
+    change.enabled
+}
+
+#[tauri::command]
+fn toggle_record_mode(app: tauri::AppHandle, session_id: Option<i64>) -> Result<bool, String> {
+    Ok(flip_record_mode(&app, session_id))
 }
 
 #[tauri::command]
@@ -103,20 +521,69 @@ fn get_record_mode(state: tauri::State<AppState>) -> Result<bool, String> {
 }
 
 #[tauri::command]
-fn get_sidecar_port(state: tauri::State<AppState>) -> u16 {
+fn set_privacy_blur(app: tauri::AppHandle, enabled: bool) {
+    apply_privacy_blur(&app, enabled);
+}
+
+#[tauri::command]
+fn get_privacy_blur(state: tauri::State<AppState>) -> bool {
+    state.privacy_blur.load(Ordering::SeqCst)
+}
+
+#[tauri::command]
+fn get_install_id(state: tauri::State<AppState>) -> Result<String, String> {
+    let mut install_id = state.install_id.lock().unwrap();
+    if let Some(id) = install_id.as_ref() {
+        return Ok(id.clone());
+    }
+
+    let id = state
+        .config
+        .read_or_create_install_id()
+        .map_err(|e| e.to_string())?;
+    *install_id = Some(id.clone());
+    Ok(id)
+}
+
+// The port the tRPC server was started on, which the frontend builds its
+// client URL from
+#[tauri::command]
+fn get_server_port(state: tauri::State<AppState>) -> u16 {
     state.config.get_port()
 }
 
 fn toggle_launchbar(app: &tauri::AppHandle) {
-    let window = app
-        .get_webview_window("quick-panel")
-        .expect("Did you label your window?");
-    if let Ok(true) = window.is_visible() {
-        let _ = window.hide();
-    } else {
-        let _ = window.show();
-        let _ = window.set_focus();
-        window.set_always_on_top(true).unwrap();
+    let Some(window) = app.get_webview_window("quick-panel") else {
+        log_error!("Can't toggle the quick panel, it doesn't exist");
+        return;
+    };
+    // When the window server can't say, showing is the safer guess: a panel
+    // shown twice is harmless, one that never opens isn't
+    let visible = window.is_visible().unwrap_or_else(|e| {
+        log_error!("Couldn't tell if the quick panel is visible: {e}");
+        false
+    });
+    if visible {
+        if let Err(e) = window.hide() {
+            log_error!("Failed to hide the quick panel: {e}");
+        }
+        return;
+    }
+
+    if panel::suppressed(app) {
+        return;
+    }
+    panel::center_on_active_monitor(app, &window);
+    if let Err(e) = window.show() {
+        log_error!("Failed to show the quick panel: {e}");
+        return;
+    }
+    // The panel is usable from here on, so the rest is best effort
+    if let Err(e) = window.set_focus() {
+        log_error!("Failed to focus the quick panel: {e}");
+    }
+    if let Err(e) = window.set_always_on_top(true) {
+        log_error!("Failed to keep the quick panel on top: {e}");
     }
 }
 
@@ -126,164 +593,450 @@ fn main() {
     // Determine if we're in dev mode
     let is_dev = cfg!(debug_assertions);
 
-    // Read sidecar port from environment variable, default to 4318
-    let sidecar_port: u16 = env::var("SIDECAR_PORT")
-        .ok()
-        .and_then(|p| p.parse().ok())
-        .unwrap_or(4318);
+    // `--profile <name>` wins over THOUGHTS_PROFILE
+    let profile = config::profile_from_args(env::args().skip(1)).or_else(|| {
+        env::var("THOUGHTS_PROFILE")
+            .ok()
+            .filter(|name| !name.is_empty())
+    });
+    // A bad config.toml, e.g. a misspelled key, ends up here. The packaged app
+    // has no terminal to print to, so the error is shown in an alert too.
+    let mut config = match Config::new(profile.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            log_error!("Failed to initialize config: {e}");
+            server::show_alert("Thoughts couldn't start", &e.to_string());
+            std::process::exit(1);
+        }
+    };
+    logging::set_os_log_enabled(config.os_log_enabled());
+    if let Err(e) = trace::init(&config) {
+        eprintln!("Failed to set up tracing: {e}");
+    }
 
-    let config = Config::new(sidecar_port).expect("Failed to initialize config");
+    // Runs only the server supervision, scheduler and control socket, with no
+    // tray, panel or shortcuts, for driving the app from scripts
+    let headless = env::args().skip(1).any(|arg| arg == "--headless");
+
+    // `wazzup toggle` and friends hand off to an already running instance
+    match control::parse_args(env::args().skip(1)) {
+        Ok(Some(command)) => match control::send(&config.get_control_socket_path(), command) {
+            Ok(true) => std::process::exit(control::EXIT_HANDLED),
+            Ok(false) => {}
+            Err(e) => {
+                log_error!("Failed to reach running instance: {e}");
+                std::process::exit(control::EXIT_FAILED);
+            }
+        },
+        Ok(None) => {}
+        Err(e) => {
+            log_error!("{e}");
+            std::process::exit(control::EXIT_USAGE);
+        }
+    }
 
     let builder = tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .setup(move |app| {
             let app_handle = app.app_handle();
 
-            // Cleanup any existing server process
-            config.cleanup_existing_server();
-
-            let icon = Image::from_bytes(include_bytes!("../icons/32x32.png"))?;
-
-            let shortcut_hint = if is_dev { "⇧+⌥+Space" } else { "⌥+Space" };
-            let open_i = MenuItem::with_id(app, "open", "Open", true, Some(shortcut_hint))?;
-            let quit_i = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&open_i, &quit_i])?;
-
-            let _tray = TrayIconBuilder::with_id("tray")
-                .icon(icon)
-                .icon_as_template(true)
-                .menu(&menu)
-                .on_menu_event(|tray, event| {
-                    let app_handle = tray.app_handle();
-                    match event.id().as_ref() {
-                        "open" => {
-                            // Always create fresh window to avoid stale data
-                            if let Some(window) = app_handle.get_webview_window("main") {
-                                let _ = window.close();
+            // Clean up servers and PID files left behind by a previous run
+            config.cleanup_all_servers();
+
+            if let Err(e) = control::listen(app_handle.clone(), &config.get_control_socket_path()) {
+                log_error!("Failed to open control socket: {e}");
+            }
+
+            // Must be read before the first snapshot of this run overwrites it
+            let recovered_state = recovery::load_previous(&config);
+
+            // Use different shortcuts for dev vs production unless one is configured
+            let default_shortcut = if is_dev {
+                Shortcut::new(Some(Modifiers::SHIFT | Modifiers::ALT), Code::Space)
+            } else {
+                Shortcut::new(Some(Modifiers::ALT), Code::Space)
+            };
+            let configured_shortcut = config.get_shortcut().and_then(|value| {
+                Shortcut::from_str(value)
+                    .map_err(|e| log_error!("Invalid shortcut {value:?}, using the default: {e}"))
+                    .ok()
+                    .map(|shortcut| (shortcut, value.to_owned()))
+            });
+            let (shortcut, shortcut_hint) = match configured_shortcut {
+                Some(configured) => configured,
+                None if is_dev => (default_shortcut, "⇧+⌥+Space".to_string()),
+                None => (default_shortcut, "⌥+Space".to_string()),
+            };
+            // The tray's Open item shows this, as it opens the main window too
+            let (main_window_shortcut, main_window_shortcut_hint) =
+                match config.get_main_window_shortcut() {
+                    Some(value) => match Shortcut::from_str(value) {
+                        Ok(shortcut) => (Some(shortcut), Some(value.to_owned())),
+                        Err(e) => {
+                            log_error!("Invalid main window shortcut {value:?}: {e}");
+                            (None, None)
+                        }
+                    },
+                    None => (
+                        Some(Shortcut::new(
+                            Some(Modifiers::ALT | Modifiers::SHIFT),
+                            Code::KeyT,
+                        )),
+                        Some("⌥+⇧+T".to_string()),
+                    ),
+                };
+            let tray_menu = TrayMenu::new(main_window_shortcut_hint.as_deref());
+
+            if !headless {
+                let menu = tray_menu.build(app)?;
+                let _tray = TrayIconBuilder::with_id(TRAY_ID)
+                    .icon(tray_menu.icon()?)
+                    .icon_as_template(true)
+                    .tooltip(tray_menu.tooltip())
+                    .menu(&menu)
+                    .on_menu_event(|tray, event| {
+                        let app_handle = tray.app_handle();
+                        match event.id().as_ref() {
+                            "open" => {
+                                if let Err(e) = focus_main_window(app_handle.clone()) {
+                                    log_error!("Failed to open the main window: {e}");
+                                }
+                            }
+                            "record_mode" => {
+                                flip_record_mode(app_handle, None);
                             }
-                            create_main_window(app_handle);
+                            "quit" => app_handle.exit(0),
+                            _ => {}
                         }
-                        "quit" => app_handle.exit(0),
-                        _ => {}
-                    }
-                })
-                .build(app_handle)
-                .unwrap();
+                    })
+                    .build(app_handle)?;
+            }
 
-            let window = app.get_webview_window("quick-panel").unwrap();
+            // The panel is left out of the static window config so headless
+            // runs never create it
+            let window = if headless {
+                None
+            } else {
+                let panel_config = app
+                    .config()
+                    .app
+                    .windows
+                    .iter()
+                    .find(|window| window.label == "quick-panel")
+                    .expect("Did you label your window?")
+                    .clone();
+                Some(WebviewWindowBuilder::from_config(app, &panel_config)?.build()?)
+            };
 
             // Configure window based on dev/prod mode
-            if is_dev {
-                // Dev mode: make it a normal window with decorations for easier debugging
-                window.set_decorations(true).unwrap();
-                window.set_always_on_top(false).unwrap();
-                // Don't hide window initially in dev mode
-            } else {
-                // Production mode: frameless, always on top
-                window.set_decorations(false).unwrap();
-                window.set_always_on_top(true).unwrap();
-                // Hide window initially in production
-                window.hide().unwrap();
+            if let Some(window) = &window {
+                let configured = if is_dev {
+                    // Dev mode: make it a normal window with decorations for easier debugging
+                    window
+                        .set_decorations(true)
+                        .and_then(|_| window.set_always_on_top(false))
+                } else {
+                    // Production mode: frameless, always on top
+                    window
+                        .set_decorations(false)
+                        .and_then(|_| window.set_always_on_top(true))
+                };
+                let shown = if config.start_hidden() {
+                    window.hide()
+                } else {
+                    window.show()
+                };
+                // The panel still works when styled wrong, so this isn't fatal
+                if let Err(e) = configured.and(shown) {
+                    log_error!("Failed to configure the quick panel: {e}");
+                }
             }
 
-            // Run sidecar tRPC server
-            let sidecar = app
-                .shell()
-                .sidecar("server")
-                .unwrap()
-                .env("SIDECAR_PORT", sidecar_port.to_string());
+            let record_mode_shortcut = match config.get_record_mode_shortcut() {
+                Some(value) => Shortcut::from_str(value)
+                    .map_err(|e| log_error!("Invalid record mode shortcut {value:?}: {e}"))
+                    .ok(),
+                None => Some(Shortcut::new(
+                    Some(Modifiers::ALT | Modifiers::SHIFT),
+                    Code::KeyR,
+                )),
+            };
+
+            let privacy_shortcut = config.get_privacy_blur_shortcut().and_then(|value| {
+                Shortcut::from_str(value)
+                    .map_err(|e| log_error!("Invalid privacy blur shortcut {value:?}: {e}"))
+                    .ok()
+            });
+
+            if let Err(e) = config.pick_port() {
+                log_error!("{e}");
+                std::process::exit(1);
+            }
 
-            let (mut rx, child) = sidecar.spawn().expect("Failed to spawn sidecar");
+            // Run sidecar tRPC server. A missing binary is expected in dev
+            // before the server has been built, so the UI still comes up there.
+            // Packaged builds have no terminal to show the error in, so the
+            // user is asked whether to try again.
+            let child = loop {
+                match spawn_server(app_handle, &config, None) {
+                    Ok(child) => break Some(child),
+                    Err(e) if is_dev => {
+                        log_error!(
+                            "Sidecar not found ({e}), run the server build step. Starting without it and retrying in the background."
+                        );
+                        break None;
+                    }
+                    Err(e) => {
+                        let message = format!("The Thoughts server failed to start: {e}");
+                        if !server::ask_retry(&message) {
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            };
 
-            // Store the PID in the file
-            config
-                .write_pid_file(child.pid())
-                .expect("Failed to write PID file");
+            // Store the PID in the file. Without it a server orphaned by a
+            // crash isn't cleaned up on the next launch, which isn't worth
+            // refusing to start over.
+            if let Some(child) = &child {
+                if let Err(e) = config.write_pid_file(child.pid()) {
+                    log_error!("Failed to write PID file: {e}");
+                }
+            }
 
             // Store the child process handle and config in state
             app.manage(AppState {
-                server: Mutex::new(Some(child)),
+                server: Mutex::new(child),
+                server_build: Mutex::new(None),
+                capture_log: CaptureLog::new(config.get_capture_log_path()),
+                record_mode: Mutex::new(RecordModeState {
+                    enabled: false,
+                    session_id: None,
+                }),
+                tray_menu,
+                privacy_blur: AtomicBool::new(false),
+                install_id: Mutex::new(None),
+                context_tasks: ContextTasks::new(
+                    config.get_context_timeout_ms(),
+                    config.get_source_timeouts().clone(),
+                ),
+                recovered_state: Mutex::new(recovered_state.clone()),
+                recent_documents: TtlCache::new(RECENT_DOCUMENTS_TTL),
+                context_cache: TtlCache::new(Duration::from_millis(
+                    config.get_context_cache_ttl_ms(),
+                )),
+                location_cache: LocationCache::default(),
+                shortcuts: ShortcutBindings::new(config.get_shortcut_debounce_ms()),
+                schedules: Schedules::load(config.get_schedules_path()),
+                next_capture_metadata: NextCaptureMetadata::default(),
+                metrics: Metrics::new(),
+                panel_auto_resize: AtomicBool::new(config.panel_auto_resize()),
+                hide_on_blur: AtomicBool::new(config.hide_on_blur()),
+                panel_suppressed_at: Mutex::new(None),
+                capture_streak: CaptureStreak::load(config.get_streak_path()),
+                input_device: InputDevicePreference::load(config.get_input_device_path()),
+                window_tracking: AtomicU64::new(0),
+                context_sources: ContextSourceMap::load(config.get_context_sources_path()),
                 config,
-                record_mode: Mutex::new(RecordModeState { enabled: false }),
             });
 
-            // Set up event handling for stdout/stderr
-            tauri::async_runtime::spawn(async move {
-                while let Some(event) = rx.recv().await {
-                    match event {
-                        CommandEvent::Stdout(line) => {
-                            println!(
-                                "{} {}",
-                                "[tRPC]".bright_blue().bold(),
-                                String::from_utf8_lossy(&line)
-                            );
-                        }
-                        CommandEvent::Stderr(line) => {
-                            println!(
-                                "{} {}",
-                                "[tRPC]".bright_red().bold(),
-                                String::from_utf8_lossy(&line)
-                            );
-                        }
-                        _ => {}
-                    }
+            if let Some(snapshot) = &recovered_state {
+                recovery::restore(app_handle, snapshot);
+            }
+            recovery::start_snapshots(app_handle.clone());
+            if is_dev {
+                server::attach_when_available(app_handle.clone());
+            }
+            schedule::start(app_handle.clone());
+            panel::listen_for_content_height(app_handle);
+            panel::watch_displays(app_handle.clone());
+            streak::listen_for_captures(app_handle);
+
+            if let Some(port) = app.state::<AppState>().config.get_metrics_port() {
+                if let Err(e) = metrics::serve(app_handle.clone(), port) {
+                    log_error!("Failed to start metrics endpoint on port {port}: {e}");
                 }
-            });
+            }
+
+            #[cfg(target_os = "macos")]
+            context::watch_accent_color(app_handle.clone());
+            #[cfg(target_os = "macos")]
+            power::watch(app_handle.clone());
+            #[cfg(target_os = "macos")]
+            context::watch_app_focus(app_handle.clone());
 
-            // Set up window to close when it loses focus (only in production)
-            if !is_dev {
+            // Set up window to close when it loses focus, unless turned off
+            // with hide_on_blur
+            if let Some(window) = &window {
                 let window_clone = window.clone();
-                window.on_window_event(move |event| match event {
-                    tauri::WindowEvent::Focused(focused) => {
-                        if !focused {
-                            let _ = window_clone.hide();
-                        }
+                window.on_window_event(move |event| {
+                    if let tauri::WindowEvent::Focused(focused) = event {
+                        panel::handle_focus_change(&window_clone, *focused);
                     }
-                    _ => {}
                 });
             }
 
-            // Use different shortcuts for dev vs production
-            let shortcut = if is_dev {
-                Shortcut::new(Some(Modifiers::SHIFT | Modifiers::ALT), Code::Space)
-            } else {
-                Shortcut::new(Some(Modifiers::ALT), Code::Space)
-            };
-
-            app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+            let show_in_dock = app.state::<AppState>().config.show_in_dock();
+            if let Err(e) = apply_activation_policy(app.handle(), show_in_dock) {
+                log_error!("Failed to set the activation policy: {e}");
+            }
 
             // Register the plugin with handlers
             app.handle().plugin(
                 tauri_plugin_global_shortcut::Builder::new()
-                    .with_handler(move |app, registered_shortcut, event| {
-                        if registered_shortcut == &shortcut {
-                            match event.state() {
-                                ShortcutState::Pressed => {
-                                    toggle_launchbar(app);
-                                }
-                                ShortcutState::Released => {
-                                    // Handle release if needed
-                                }
-                            }
-                        }
+                    .with_handler(|app, registered_shortcut, event| {
+                        shortcuts::handle_shortcut(app, registered_shortcut, event);
                     })
                     .build(),
             )?;
-            app.global_shortcut().register(shortcut)?;
+            if headless {
+                return Ok(());
+            }
+            let state = app.state::<AppState>();
+
+            // The panel's first query fails if it opens before the server is
+            // listening, so the shortcut waits for it. In dev the sidecar may
+            // not be built yet, and then there's nothing to wait for.
+            let server_started = state.server.lock().unwrap().is_some();
+            let port = state.config.get_port();
+            let app_handle = app.handle().clone();
+            std::thread::spawn(move || {
+                if server_started && !server::wait_until_ready(&app_handle, port) {
+                    return;
+                }
+                let state = app_handle.state::<AppState>();
+                // Another app holding the combo shouldn't keep the app from starting
+                if let Err(e) =
+                    state
+                        .shortcuts
+                        .register(&app_handle, ShortcutAction::TogglePanel, shortcut)
+                {
+                    shortcuts::report_registration_failure(&app_handle, &shortcut_hint, &e);
+                }
+            });
+
+            // Each shortcut is registered on its own, so one that's taken
+            // doesn't keep the others from working
+            if let Some(main_window_shortcut) = main_window_shortcut {
+                if let Err(e) = state.shortcuts.register(
+                    app.handle(),
+                    ShortcutAction::OpenMainWindow,
+                    main_window_shortcut,
+                ) {
+                    log_error!("Failed to register main window shortcut: {e}");
+                    state.tray_menu.update(app.handle(), |tray_state| {
+                        tray_state.main_window_shortcut_hint = None
+                    });
+                }
+            }
+
+            if let Some(record_mode_shortcut) = record_mode_shortcut {
+                if let Err(e) = state.shortcuts.register(
+                    app.handle(),
+                    ShortcutAction::ToggleRecordMode,
+                    record_mode_shortcut,
+                ) {
+                    log_error!("Failed to register record mode shortcut: {e}");
+                }
+            }
+
+            // The privacy shortcut is optional, so a conflict shouldn't abort startup
+            if let Some(privacy_shortcut) = privacy_shortcut {
+                if let Err(e) = state.shortcuts.register(
+                    app.handle(),
+                    ShortcutAction::TogglePrivacyBlur,
+                    privacy_shortcut,
+                ) {
+                    log_error!("Failed to register privacy blur shortcut: {e}");
+                }
+            }
 
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             open_main_window,
+            focus_main_window,
             open_replay_window,
             close_quickpanel,
+            show_panel_toast,
             active_arc_url,
+            active_browser_url,
             get_spotify_track,
+            get_playback_queue,
+            get_now_playing,
+            get_page_scroll_context,
             get_focused_app,
+            get_context_snapshot,
+            get_context_by_name,
+            get_ide_status,
+            get_last_terminal_command,
             get_location,
+            get_accent_color,
+            get_recent_documents,
+            set_source_timeout,
             toggle_record_mode,
             get_record_mode,
-            get_sidecar_port
+            get_server_port,
+            set_privacy_blur,
+            get_privacy_blur,
+            get_install_id,
+            record_context,
+            list_server_builds,
+            switch_server_build,
+            restart_server,
+            is_server_alive,
+            set_tray_badge,
+            recover_state,
+            resume_record_session,
+            check_clock_sync,
+            register_shortcuts,
+            diagnose_shortcut_owner,
+            get_ambient_level,
+            add_schedule,
+            list_schedules,
+            remove_schedule,
+            get_context_at,
+            set_next_capture_metadata,
+            take_next_capture_metadata,
+            get_storage_usage,
+            cleanup_old_artifacts,
+            get_git_diff_summary,
+            get_metrics,
+            set_panel_auto_resize,
+            set_quickpanel_size,
+            set_hide_on_blur,
+            get_notes_app_context,
+            get_capture_streak,
+            list_input_devices,
+            set_recording_input_device,
+            get_active_window_bounds,
+            set_window_bounds_tracking,
+            set_trace_level,
+            get_log_path,
+            get_system_volume,
+            get_wifi_ssid,
+            get_clipboard_text,
+            capture_focused_window,
+            get_idle_seconds,
+            get_context_source_map,
+            set_context_sources,
+            get_context_sources_for_app,
+            migrate_config_dir,
+            get_config_dir,
+            open_config_dir,
+            get_capability_matrix,
+            check_permissions,
+            request_permissions,
+            get_launch_at_login,
+            set_launch_at_login,
+            get_app_version,
+            set_show_in_dock,
+            check_for_update,
+            get_email_context,
+            reconcile_server_state,
+            set_shortcut,
+            try_register_shortcut,
+            is_shortcut_registered
         ]);
 
     builder
@@ -291,11 +1044,19 @@ fn main() {
         .expect("error while building tauri application")
         .run(|app, event| match event {
             RunEvent::ExitRequested { .. } | RunEvent::Exit => {
+                #[cfg(target_os = "macos")]
+                context::stop_watching_app_focus();
                 if let Some(state) = app.try_state::<AppState>() {
-                    if let Some(mut child) = state.server.lock().unwrap().take() {
-                        let _ = child.kill();
+                    state.context_tasks.drain(CONTEXT_DRAIN_TIMEOUT);
+                    if let Some(child) = state.server.lock().unwrap().take() {
+                        tracing::info!(pid = child.pid(), "stopping server on exit");
+                        let timeout = Duration::from_millis(state.config.get_shutdown_timeout_ms());
+                        state
+                            .config
+                            .terminate_server_gracefully(child.pid(), timeout);
                     }
-                    state.config.cleanup_pid_file();
+                    let _ = std::fs::remove_file(state.config.get_control_socket_path());
+                    recovery::clear(&state.config);
                 }
             }
             _ => {}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use tauri::{AppHandle, Manager};
+
+use crate::context::SourceCounts;
+use crate::shortcuts::ShortcutAction;
+use crate::AppState;
+
+// App-side usage counters. They live only in memory, so they reset on restart.
+pub struct Metrics {
+    started_at: Instant,
+    shortcut_triggers: Mutex<HashMap<ShortcutAction, u64>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics {
+            started_at: Instant::now(),
+            shortcut_triggers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn count_shortcut(&self, action: ShortcutAction) {
+        *self
+            .shortcut_triggers
+            .lock()
+            .unwrap()
+            .entry(action)
+            .or_default() += 1;
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct MetricsReport {
+    uptime_seconds: u64,
+    context_sources: HashMap<String, SourceCounts>,
+    shortcut_triggers: HashMap<ShortcutAction, u64>,
+}
+
+fn report(state: &AppState) -> MetricsReport {
+    MetricsReport {
+        uptime_seconds: state.metrics.started_at.elapsed().as_secs(),
+        context_sources: state.context_tasks.counts(),
+        shortcut_triggers: state.metrics.shortcut_triggers.lock().unwrap().clone(),
+    }
+}
+
+#[tauri::command]
+pub fn get_metrics(state: tauri::State<AppState>) -> MetricsReport {
+    report(&state)
+}
+
+// Prometheus text exposition format
+fn render(report: &MetricsReport) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(out, "# TYPE thoughts_uptime_seconds gauge");
+    let _ = writeln!(out, "thoughts_uptime_seconds {}", report.uptime_seconds);
+
+    let mut sources: Vec<_> = report.context_sources.iter().collect();
+    sources.sort_by(|a, b| a.0.cmp(b.0));
+    let _ = writeln!(out, "# TYPE thoughts_context_gathers_total counter");
+    for (source, counts) in &sources {
+        let _ = writeln!(
+            out,
+            "thoughts_context_gathers_total{{source=\"{source}\"}} {}",
+            counts.gathers
+        );
+    }
+    let _ = writeln!(out, "# TYPE thoughts_context_errors_total counter");
+    for (source, counts) in &sources {
+        let _ = writeln!(
+            out,
+            "thoughts_context_errors_total{{source=\"{source}\"}} {}",
+            counts.errors
+        );
+    }
+
+    let _ = writeln!(out, "# TYPE thoughts_shortcut_triggers_total counter");
+    for (action, count) in &report.shortcut_triggers {
+        let _ = writeln!(
+            out,
+            "thoughts_shortcut_triggers_total{{action=\"{}\"}} {count}",
+            action.as_str()
+        );
+    }
+
+    out
+}
+
+// Serves GET /metrics on localhost only. Anything else gets a 404.
+pub fn serve(app: AppHandle, port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request_line = String::new();
+            let Ok(reader) = stream.try_clone() else {
+                continue;
+            };
+            if BufReader::new(reader).read_line(&mut request_line).is_err() {
+                continue;
+            }
+
+            let response = match request_line.split_whitespace().collect::<Vec<_>>()[..] {
+                ["GET", "/metrics", ..] => {
+                    let body = render(&report(&app.state::<AppState>()));
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                }
+                _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string(),
+            };
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    Ok(())
+}
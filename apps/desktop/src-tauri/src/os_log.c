@@ -0,0 +1,8 @@
+#include <os/log.h>
+#include <stdint.h>
+
+// os_log is a macro that needs a compile-time format string, so Rust calls
+// through this instead. %{public}s keeps the message from being redacted.
+void thoughts_os_log(os_log_t log, uint8_t type, const char *message) {
+    os_log_with_type(log, (os_log_type_t)type, "%{public}s", message);
+}
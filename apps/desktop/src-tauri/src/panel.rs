@@ -0,0 +1,330 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{
+    AppHandle, Listener, LogicalPosition, LogicalSize, Manager, Monitor, PhysicalPosition,
+};
+
+use crate::context::{front_window_fullscreen, frontmost_bundle_id};
+use crate::AppState;
+
+const PANEL_MIN_HEIGHT: f64 = 80.0;
+const PANEL_MAX_HEIGHT: f64 = 800.0;
+
+// The panel reports its content height in logical pixels whenever it changes.
+// With auto-resize on, the window follows it; its top-left corner stays put so
+// the panel grows downwards from where the user expects it.
+pub fn listen_for_content_height(app: &AppHandle) {
+    let handle = app.clone();
+    app.listen_any("panel-content-height", move |event| {
+        let Ok(height) = serde_json::from_str::<f64>(event.payload()) else {
+            return;
+        };
+        let enabled = handle
+            .try_state::<AppState>()
+            .is_some_and(|state| state.panel_auto_resize.load(Ordering::SeqCst));
+        if enabled {
+            resize_to_content(&handle, height);
+        }
+    });
+}
+
+fn resize_to_content(app: &AppHandle, content_height: f64) {
+    let Some(window) = app.get_webview_window("quick-panel") else {
+        return;
+    };
+    let (Ok(size), Ok(position), Ok(scale)) = (
+        window.outer_size(),
+        window.outer_position(),
+        window.scale_factor(),
+    ) else {
+        return;
+    };
+
+    // Never run past the bottom of the display the panel is on
+    let mut max_height = PANEL_MAX_HEIGHT;
+    if let Ok(Some(monitor)) = window.current_monitor() {
+        let monitor_bottom = monitor.position().y as f64 + monitor.size().height as f64;
+        max_height = max_height.min((monitor_bottom - position.y as f64) / scale);
+    }
+
+    let height = content_height.clamp(PANEL_MIN_HEIGHT, max_height.max(PANEL_MIN_HEIGHT));
+    let width = size.to_logical::<f64>(scale).width;
+    let _ = window.set_size(LogicalSize::new(width, height));
+}
+
+// Pressing the shortcut again this soon after the panel was held back shows
+// it anyway
+const SUPPRESSION_OVERRIDE_WINDOW: Duration = Duration::from_millis(1500);
+
+// How long the shortcut waits on the fullscreen script before showing the
+// panel anyway
+const FULLSCREEN_CHECK_TIMEOUT: Duration = Duration::from_millis(150);
+
+// Runs the fullscreen check on its own thread, so a slow or hung script only
+// costs the shortcut FULLSCREEN_CHECK_TIMEOUT. An answer that's late counts as
+// not fullscreen.
+fn front_window_fullscreen_within(app: &AppHandle, timeout: Duration) -> bool {
+    let (tx, rx) = mpsc::channel();
+    let app = app.clone();
+    thread::spawn(move || {
+        let state = app.state::<AppState>();
+        let _ = tx.send(front_window_fullscreen(&state.context_tasks).unwrap_or(false));
+    });
+    rx.recv_timeout(timeout).unwrap_or(false)
+}
+
+// Do not disturb: keeps the panel from popping up over one of the configured
+// apps or a fullscreen window, e.g. while presenting. The frontmost app comes
+// from the app focus watcher, so only the fullscreen check asks the system,
+// and only when it's configured.
+pub fn suppressed(app: &AppHandle) -> bool {
+    let Some(state) = app.try_state::<AppState>() else {
+        return false;
+    };
+    let apps = state.config.get_suppress_in_apps();
+    let check_fullscreen = state.config.suppress_in_fullscreen();
+    if apps.is_empty() && !check_fullscreen {
+        return false;
+    }
+
+    let mut suppressed_at = state.panel_suppressed_at.lock().unwrap();
+    if suppressed_at
+        .take()
+        .is_some_and(|at| at.elapsed() < SUPPRESSION_OVERRIDE_WINDOW)
+    {
+        return false;
+    }
+
+    let reason = match frontmost_bundle_id() {
+        Some(bundle_id) if apps.contains(&bundle_id) => Some(bundle_id),
+        _ if check_fullscreen && front_window_fullscreen_within(app, FULLSCREEN_CHECK_TIMEOUT) => {
+            Some("fullscreen window".to_owned())
+        }
+        _ => None,
+    };
+    let Some(reason) = reason else {
+        return false;
+    };
+    tracing::info!(reason = %reason, "quick panel held back, press again to show it");
+    *suppressed_at = Some(Instant::now());
+    true
+}
+
+// The display the user is working on, going by the cursor
+fn active_monitor(app: &AppHandle) -> Option<Monitor> {
+    let cursor = app.cursor_position().ok()?;
+    app.available_monitors().ok()?.into_iter().find(|monitor| {
+        let position = monitor.position();
+        let size = monitor.size();
+        (position.x as f64..position.x as f64 + size.width as f64).contains(&cursor.x)
+            && (position.y as f64..position.y as f64 + size.height as f64).contains(&cursor.y)
+    })
+}
+
+// Centers the panel on the active display before it's shown, falling back to
+// the primary one when the cursor can't be located
+pub fn center_on_active_monitor(app: &AppHandle, window: &tauri::WebviewWindow) {
+    let monitor = active_monitor(app).or_else(|| app.primary_monitor().ok().flatten());
+    let (Some(monitor), Ok(size)) = (monitor, window.outer_size()) else {
+        return;
+    };
+
+    let position = monitor.position();
+    let area = monitor.size();
+    let x = position.x + (area.width as i32 - size.width as i32) / 2;
+    let y = position.y + (area.height as i32 - size.height as i32) / 2;
+    if let Err(e) = window.set_position(PhysicalPosition::new(x, y)) {
+        log_error!("Failed to move the quick panel: {e}");
+    }
+}
+
+// How often the display layout is compared for hotplug changes. Tauri has no
+// event for it, and listing monitors is cheap.
+const DISPLAY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Position, size and scale of every connected display, to tell when one was
+// added, removed or rearranged
+fn display_layout(app: &AppHandle) -> Vec<(i32, i32, u32, u32, u64)> {
+    app.available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(|monitor| {
+            let position = monitor.position();
+            let size = monitor.size();
+            let scale = monitor.scale_factor().to_bits();
+            (position.x, position.y, size.width, size.height, scale)
+        })
+        .collect()
+}
+
+// Whether the middle of the window is on any connected display
+fn on_screen(monitors: &[Monitor], window: &tauri::WebviewWindow) -> bool {
+    let (Ok(position), Ok(size)) = (window.outer_position(), window.outer_size()) else {
+        return true;
+    };
+    let center_x = position.x + size.width as i32 / 2;
+    let center_y = position.y + size.height as i32 / 2;
+    monitors.iter().any(|monitor| {
+        let origin = monitor.position();
+        let area = monitor.size();
+        (origin.x..origin.x + area.width as i32).contains(&center_x)
+            && (origin.y..origin.y + area.height as i32).contains(&center_y)
+    })
+}
+
+fn center_on_primary_monitor(app: &AppHandle, window: &tauri::WebviewWindow) {
+    let (Ok(Some(monitor)), Ok(size)) = (app.primary_monitor(), window.outer_size()) else {
+        return;
+    };
+    let position = monitor.position();
+    let area = monitor.size();
+    let x = position.x + (area.width as i32 - size.width as i32) / 2;
+    let y = position.y + (area.height as i32 - size.height as i32) / 2;
+    if let Err(e) = window.set_position(PhysicalPosition::new(x, y)) {
+        log_error!("Failed to move {} back on screen: {e}", window.label());
+    }
+}
+
+// Unplugging a display can leave the panel or main window stranded where no
+// display is anymore, so showing it looks like a no-op. Whenever the layout
+// changes, windows that ended up off every display are recentered on the
+// primary one.
+pub fn watch_displays(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut last_layout = display_layout(&app);
+
+        loop {
+            std::thread::sleep(DISPLAY_POLL_INTERVAL);
+
+            let layout = display_layout(&app);
+            if layout == last_layout || layout.is_empty() {
+                continue;
+            }
+            last_layout = layout;
+            tracing::info!(displays = last_layout.len(), "display layout changed");
+
+            let Ok(monitors) = app.available_monitors() else {
+                continue;
+            };
+            for label in ["quick-panel", "main"] {
+                let Some(window) = app.get_webview_window(label) else {
+                    continue;
+                };
+                if !on_screen(&monitors, &window) {
+                    tracing::info!(window = label, "recentering window left off screen");
+                    center_on_primary_monitor(&app, &window);
+                }
+            }
+        }
+    });
+}
+
+#[derive(serde::Serialize)]
+pub struct PanelSize {
+    width: f64,
+    height: f64,
+}
+
+// Resizes the panel to the requested logical size, shrunk to fit the display
+// it's on, and returns the size actually applied. The panel is moved back on
+// screen if it would spill over the edge, or centered when asked.
+#[tauri::command]
+pub fn set_quickpanel_size(
+    app: AppHandle,
+    width: f64,
+    height: f64,
+    recenter: Option<bool>,
+) -> Result<PanelSize, String> {
+    if !width.is_finite() || !height.is_finite() {
+        return Err("The panel size must be a finite number".to_string());
+    }
+    let window = app
+        .get_webview_window("quick-panel")
+        .ok_or("The quick panel doesn't exist")?;
+    let monitor = window
+        .current_monitor()
+        .ok()
+        .flatten()
+        .or_else(|| active_monitor(&app))
+        .ok_or("There's no display to fit the panel to")?;
+
+    let scale = monitor.scale_factor();
+    let area_position = monitor.position().to_logical::<f64>(scale);
+    let area = monitor.size().to_logical::<f64>(scale);
+    let width = width.clamp(1.0, area.width);
+    let height = height.clamp(PANEL_MIN_HEIGHT.min(area.height), area.height);
+    window
+        .set_size(LogicalSize::new(width, height))
+        .map_err(|e| e.to_string())?;
+
+    let (x, y) = if recenter.unwrap_or(false) {
+        (
+            area_position.x + (area.width - width) / 2.0,
+            area_position.y + (area.height - height) / 2.0,
+        )
+    } else {
+        let position = window
+            .outer_position()
+            .map_err(|e| e.to_string())?
+            .to_logical::<f64>(scale);
+        (
+            position
+                .x
+                .clamp(area_position.x, area_position.x + area.width - width),
+            position
+                .y
+                .clamp(area_position.y, area_position.y + area.height - height),
+        )
+    };
+    window
+        .set_position(LogicalPosition::new(x, y))
+        .map_err(|e| e.to_string())?;
+
+    Ok(PanelSize { width, height })
+}
+
+#[tauri::command]
+pub fn set_panel_auto_resize(state: tauri::State<AppState>, enabled: bool) {
+    state.panel_auto_resize.store(enabled, Ordering::SeqCst);
+}
+
+// macOS briefly takes focus away from the panel when it opens a native menu or
+// file picker, so losing focus only hides the panel if it stays lost this long
+const BLUR_HIDE_DELAY: Duration = Duration::from_millis(150);
+
+// Bumped on every focus change, so a pending hide can tell it's been
+// superseded
+static FOCUS_CHANGES: AtomicU64 = AtomicU64::new(0);
+
+pub fn handle_focus_change(window: &tauri::WebviewWindow, focused: bool) {
+    let generation = FOCUS_CHANGES.fetch_add(1, Ordering::SeqCst) + 1;
+    if focused {
+        return;
+    }
+    let hide_on_blur = window
+        .try_state::<AppState>()
+        .is_some_and(|state| state.hide_on_blur.load(Ordering::SeqCst));
+    if !hide_on_blur {
+        return;
+    }
+
+    let window = window.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(BLUR_HIDE_DELAY).await;
+        if FOCUS_CHANGES.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        if let Err(e) = window.hide() {
+            log_error!("Failed to hide the quick panel: {e}");
+        }
+    });
+}
+
+// With this off the panel stays up when another window takes focus, until
+// it's closed or toggled with the shortcut. Lasts until the app restarts.
+#[tauri::command]
+pub fn set_hide_on_blur(state: tauri::State<AppState>, enabled: bool) {
+    state.hide_on_blur.store(enabled, Ordering::SeqCst);
+}
@@ -0,0 +1,49 @@
+#include <CoreFoundation/CoreFoundation.h>
+#include <IOKit/IOMessage.h>
+#include <IOKit/pwr_mgt/IOPMLib.h>
+
+// Events passed to the callback, matching POWER_WILL_SLEEP and POWER_DID_WAKE
+// in src/power.rs
+#define THOUGHTS_POWER_WILL_SLEEP 0
+#define THOUGHTS_POWER_DID_WAKE 1
+
+typedef void (*thoughts_power_callback)(int event);
+
+static io_connect_t root_port = MACH_PORT_NULL;
+static thoughts_power_callback callback = NULL;
+
+static void power_changed(void *refcon, io_service_t service, natural_t message_type,
+                          void *message_argument) {
+    switch (message_type) {
+    case kIOMessageCanSystemSleep:
+        IOAllowPowerChange(root_port, (long)message_argument);
+        break;
+    case kIOMessageSystemWillSleep:
+        // Sleep waits for this acknowledgement, so the callback gets to finish
+        // what it's doing first
+        callback(THOUGHTS_POWER_WILL_SLEEP);
+        IOAllowPowerChange(root_port, (long)message_argument);
+        break;
+    case kIOMessageSystemHasPoweredOn:
+        callback(THOUGHTS_POWER_DID_WAKE);
+        break;
+    }
+}
+
+// Registers for system sleep and wake and runs the calling thread's run loop
+// to deliver them, so it only returns if registration fails. The C API is the
+// same notification NSWorkspace builds on, without needing Objective-C.
+int thoughts_watch_power(thoughts_power_callback cb) {
+    IONotificationPortRef notify_port;
+    io_object_t notifier;
+
+    callback = cb;
+    root_port = IORegisterForSystemPower(NULL, &notify_port, power_changed, &notifier);
+    if (root_port == MACH_PORT_NULL) {
+        return -1;
+    }
+    CFRunLoopAddSource(CFRunLoopGetCurrent(), IONotificationPortGetRunLoopSource(notify_port),
+                       kCFRunLoopCommonModes);
+    CFRunLoopRun();
+    return 0;
+}
@@ -0,0 +1,77 @@
+// Quiets the server around system sleep. A server suspended mid-write can
+// leave its data half written, so it's asked to flush and pause before the
+// machine sleeps, and checked on after it wakes.
+use std::ffi::c_int;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::server::{is_server_alive, restart_server, send_server_command};
+use crate::AppState;
+
+// Sleep is held off while the server flushes, so this is kept short. macOS
+// gives up waiting after 30s anyway.
+const QUIESCE_GRACE: Duration = Duration::from_millis(500);
+
+// Values from src/power.c
+const POWER_WILL_SLEEP: c_int = 0;
+const POWER_DID_WAKE: c_int = 1;
+
+#[link(name = "IOKit", kind = "framework")]
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    // Defined in src/power.c
+    fn thoughts_watch_power(callback: extern "C" fn(c_int)) -> c_int;
+}
+
+// The C callback has no context pointer, so the handle is kept here
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+#[derive(Clone, serde::Serialize)]
+struct ServerResumed {
+    // The server had died while asleep and was started again
+    restarted: bool,
+}
+
+extern "C" fn power_changed(event: c_int) {
+    let Some(app) = APP.get() else {
+        return;
+    };
+    let state = app.state::<AppState>();
+    match event {
+        POWER_WILL_SLEEP => {
+            tracing::info!("system going to sleep, quiescing server");
+            send_server_command(&state, "quiesce");
+            thread::sleep(QUIESCE_GRACE);
+        }
+        POWER_DID_WAKE => {
+            let restarted = !is_server_alive(state.clone());
+            if restarted {
+                tracing::warn!("server died during sleep, restarting");
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = restart_server(app).await {
+                        log_error!("Failed to restart server after wake: {e}");
+                    }
+                });
+            } else {
+                tracing::info!("system woke, resuming server");
+                send_server_command(&state, "resume");
+            }
+            let _ = app.emit("server-resumed", ServerResumed { restarted });
+        }
+        _ => {}
+    }
+}
+
+pub fn watch(app: AppHandle) {
+    if APP.set(app).is_err() {
+        return;
+    }
+    thread::spawn(|| {
+        if unsafe { thoughts_watch_power(power_changed) } != 0 {
+            log_error!("Failed to register for sleep notifications");
+        }
+    });
+}
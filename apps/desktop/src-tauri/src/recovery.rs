@@ -0,0 +1,132 @@
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, PhysicalPosition};
+
+use crate::config::{write_atomic, Config};
+use crate::AppState;
+
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(10);
+
+// Set on clean shutdown so the snapshot thread can't recreate the file
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct WindowGeometry {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+// Transient state worth carrying over a crash. Process handles like the
+// server's CommandChild are deliberately left out; they're recreated on launch.
+#[derive(Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct StateSnapshot {
+    record_mode: bool,
+    record_session_id: Option<i64>,
+    panel_geometry: Option<WindowGeometry>,
+}
+
+fn capture(app: &AppHandle) -> Option<StateSnapshot> {
+    let state = app.try_state::<AppState>()?;
+    let (record_mode, record_session_id) = {
+        let record_mode = state.record_mode.lock().unwrap();
+        (record_mode.enabled, record_mode.session_id)
+    };
+
+    let panel_geometry = app.get_webview_window("quick-panel").and_then(|window| {
+        let position = window.outer_position().ok()?;
+        let size = window.outer_size().ok()?;
+        Some(WindowGeometry {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+        })
+    });
+
+    Some(StateSnapshot {
+        record_mode,
+        record_session_id,
+        panel_geometry,
+    })
+}
+
+// The snapshot file only outlives the process when it didn't exit cleanly, so
+// finding one at startup means the previous run crashed
+pub fn load_previous(config: &Config) -> Option<StateSnapshot> {
+    let content = fs::read_to_string(config.get_state_snapshot_path()).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub fn clear(config: &Config) {
+    STOPPED.store(true, Ordering::SeqCst);
+    let _ = fs::remove_file(config.get_state_snapshot_path());
+}
+
+pub fn restore(app: &AppHandle, snapshot: &StateSnapshot) {
+    if let (Some(geometry), Some(window)) = (
+        &snapshot.panel_geometry,
+        app.get_webview_window("quick-panel"),
+    ) {
+        let _ = window.set_position(PhysicalPosition::new(geometry.x, geometry.y));
+    }
+}
+
+// Periodically writes the current state, skipping the write when nothing
+// changed since the last one
+pub fn start_snapshots(app: AppHandle) {
+    thread::spawn(move || {
+        let mut last_written: Option<StateSnapshot> = None;
+        while !STOPPED.load(Ordering::SeqCst) {
+            if let Some(snapshot) = capture(&app) {
+                if last_written.as_ref() != Some(&snapshot) {
+                    let state = app.state::<AppState>();
+                    let written = serde_json::to_string(&snapshot)
+                        .map_err(|e| e.to_string())
+                        .and_then(|json| {
+                            write_atomic(&state.config.get_state_snapshot_path(), &json)
+                                .map_err(|e| e.to_string())
+                        });
+                    match written {
+                        Ok(()) => last_written = Some(snapshot),
+                        Err(e) => log_error!("Failed to write state snapshot: {e}"),
+                    }
+                }
+            }
+            thread::sleep(SNAPSHOT_INTERVAL);
+        }
+    });
+}
+
+// Returns the state left behind by a crashed previous run, if any. The
+// frontend uses this to offer resuming an interrupted record session.
+#[tauri::command]
+pub fn recover_state(state: tauri::State<AppState>) -> Option<StateSnapshot> {
+    state.recovered_state.lock().unwrap().clone()
+}
+
+#[tauri::command]
+pub fn resume_record_session(app: AppHandle, state: tauri::State<AppState>) -> Result<i64, String> {
+    let session_id = state
+        .recovered_state
+        .lock()
+        .unwrap()
+        .take()
+        .filter(|snapshot| snapshot.record_mode)
+        .and_then(|snapshot| snapshot.record_session_id)
+        .ok_or("No interrupted record session to resume")?;
+
+    {
+        let mut record_mode = state.record_mode.lock().unwrap();
+        record_mode.enabled = true;
+        record_mode.session_id = Some(session_id);
+    }
+    state
+        .tray_menu
+        .update(&app, |tray_state| tray_state.record_mode = true);
+
+    Ok(session_id)
+}
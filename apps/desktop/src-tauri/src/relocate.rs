@@ -0,0 +1,258 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager};
+
+use crate::config::{default_config_dir, write_atomic, RELOCATION_POINTER_FILE};
+use crate::server::{active_build_path, spawn_server};
+use crate::AppState;
+
+// Recreated by the running app, so they stay behind and are regenerated in
+// the new location after the restart
+fn is_regenerated(name: &str) -> bool {
+    name == "control.sock"
+        || name == "state.json"
+        || name == RELOCATION_POINTER_FILE
+        || (name.starts_with("server-") && name.ends_with(".pid"))
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    if from.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+fn remove_recursive(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+// Renames when possible, and copies then deletes when the destination is on
+// another volume
+fn move_entry(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            if let Err(e) = copy_recursive(from, to) {
+                let _ = remove_recursive(to);
+                return Err(e);
+            }
+            remove_recursive(from)
+        }
+        result => result,
+    }
+}
+
+fn check_destination(current: &Path, new_path: &Path) -> Result<(), String> {
+    if !new_path.is_absolute() {
+        return Err("The new config dir must be an absolute path".to_string());
+    }
+    if new_path.starts_with(current) || current.starts_with(new_path) {
+        return Err("The new config dir can't contain or be inside the current one".to_string());
+    }
+
+    fs::create_dir_all(new_path)
+        .map_err(|e| format!("Couldn't create {}: {e}", new_path.display()))?;
+    let probe = new_path.join(".write-test");
+    fs::write(&probe, b"").map_err(|e| format!("{} isn't writable: {e}", new_path.display()))?;
+    let _ = fs::remove_file(&probe);
+    Ok(())
+}
+
+fn entries_to_move(current: &Path, new_path: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(current).map_err(|e| e.to_string())? {
+        let name = entry.map_err(|e| e.to_string())?.file_name();
+        if is_regenerated(&name.to_string_lossy()) {
+            continue;
+        }
+        if new_path.join(&name).exists() {
+            return Err(format!(
+                "{} already exists in the new config dir",
+                name.to_string_lossy()
+            ));
+        }
+        names.push(PathBuf::from(name));
+    }
+    Ok(names)
+}
+
+// The pointer lives in the default dir so it's found on the next launch
+// wherever the config currently is
+fn write_relocation_pointer(new_path: &Path) -> io::Result<()> {
+    let default_dir = default_config_dir()?;
+    let pointer_path = default_dir.join(RELOCATION_POINTER_FILE);
+    if new_path == default_dir {
+        return match fs::remove_file(&pointer_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    fs::create_dir_all(&default_dir)?;
+    write_atomic(&pointer_path, &new_path.to_string_lossy())
+}
+
+#[derive(serde::Serialize)]
+pub struct ConfigDirInfo {
+    path: PathBuf,
+    // The configured dir wasn't writable, so this run uses one under the
+    // temp dir that won't persist
+    temporary: bool,
+}
+
+#[tauri::command]
+pub fn get_config_dir(state: tauri::State<AppState>) -> ConfigDirInfo {
+    ConfigDirInfo {
+        path: state.config.get_config_dir().to_path_buf(),
+        temporary: state.config.is_temporary(),
+    }
+}
+
+// Why open_config_dir couldn't open a folder, tagged like the other command
+// errors, e.g. `{ "kind": "not_found", "detail": "/Users/.../logs" }`
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum OpenDirError {
+    NotFound(PathBuf),
+    // The subpath is absolute or climbs out of the config dir
+    OutsideConfigDir(PathBuf),
+    Failed(String),
+}
+
+impl std::fmt::Display for OpenDirError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenDirError::NotFound(path) => write!(f, "{} doesn't exist", path.display()),
+            OpenDirError::OutsideConfigDir(path) => {
+                write!(f, "{} isn't inside the config dir", path.display())
+            }
+            OpenDirError::Failed(message) => write!(f, "Couldn't open the folder: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for OpenDirError {}
+
+// Shows the config dir, or a folder inside it like `logs`, in Finder or the
+// platform's file manager, for poking at PID files, logs and config
+#[tauri::command]
+pub fn open_config_dir(
+    state: tauri::State<AppState>,
+    subpath: Option<PathBuf>,
+) -> Result<(), OpenDirError> {
+    let mut path = state.config.get_config_dir().to_path_buf();
+    if let Some(subpath) = subpath {
+        let inside = subpath
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)));
+        if !inside {
+            return Err(OpenDirError::OutsideConfigDir(subpath));
+        }
+        path.push(subpath);
+    }
+    if !path.is_dir() {
+        return Err(OpenDirError::NotFound(path));
+    }
+    tauri_plugin_opener::open_path(&path, None::<&str>)
+        .map_err(|e| OpenDirError::Failed(e.to_string()))
+}
+
+// Moves everything the app manages into `new_path`, then restarts so every
+// part of the app picks up the new location. The server is stopped for the
+// move and comes back with the restart. If anything fails, the files already
+// moved are put back and the server restarted where it was.
+#[tauri::command]
+pub async fn migrate_config_dir(app: AppHandle, new_path: PathBuf) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        relocate_config_dir(&app, &state, new_path)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn relocate_config_dir(app: &AppHandle, state: &AppState, new_path: PathBuf) -> Result<(), String> {
+    // The restarted app would read the variable again and find an empty dir
+    if std::env::var_os("THOUGHTS_CONFIG_PATH").is_some() {
+        return Err(format!(
+            "THOUGHTS_CONFIG_PATH is set and takes precedence; move the directory yourself and set it to {}",
+            new_path.display()
+        ));
+    }
+
+    // The relocation pointer moves the dir every profile lives under, so a
+    // single profile can't be moved on its own
+    if let Some(profile) = state.config.get_profile() {
+        return Err(format!(
+            "Profile {profile} can't be moved on its own; run without a profile to move the whole config dir"
+        ));
+    }
+
+    let current = state.config.get_config_dir().to_path_buf();
+    check_destination(&current, &new_path)?;
+    let names = entries_to_move(&current, &new_path)?;
+
+    let _span = tracing::info_span!("migrate_config_dir", to = %new_path.display()).entered();
+    let mut server = state.server.lock().unwrap();
+    let was_running = match server.take() {
+        Some(child) => {
+            // Gives the server the chance to finish its writes before its
+            // data is moved out from under it
+            tracing::info!(pid = child.pid(), "stopping server for migration");
+            let timeout = Duration::from_millis(state.config.get_shutdown_timeout_ms());
+            state
+                .config
+                .terminate_server_gracefully(child.pid(), timeout);
+            true
+        }
+        None => false,
+    };
+
+    let mut moved: Vec<&PathBuf> = Vec::new();
+    let mut result = Ok(());
+    for name in &names {
+        if let Err(e) = move_entry(&current.join(name), &new_path.join(name)) {
+            result = Err(format!("Failed to move {}: {e}", name.display()));
+            break;
+        }
+        moved.push(name);
+    }
+    if result.is_ok() {
+        result = write_relocation_pointer(&new_path)
+            .map_err(|e| format!("Couldn't record the new config dir: {e}"));
+    }
+
+    if let Err(e) = result {
+        for name in moved.iter().rev() {
+            if let Err(e) = move_entry(&new_path.join(name), &current.join(name)) {
+                log_error!("Failed to move {} back: {e}", name.display());
+            }
+        }
+
+        if was_running {
+            let binary = active_build_path(state);
+            let child = spawn_server(app, &state.config, binary.as_deref())?;
+            if let Err(e) = state.config.write_pid_file(child.pid()) {
+                log_error!("Failed to write PID file: {e}");
+            }
+            *server = Some(child);
+        }
+        return Err(e);
+    }
+    drop(server);
+
+    tracing::info!(entries = moved.len(), "config dir migrated, restarting");
+    app.request_restart();
+    Ok(())
+}
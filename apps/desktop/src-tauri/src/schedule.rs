@@ -0,0 +1,381 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Local, Timelike};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::config::write_atomic;
+use crate::AppState;
+
+// Checked a few times a minute so a trigger is never more than this late
+const TICK_INTERVAL: Duration = Duration::from_secs(15);
+
+// Five-field cron expression: minute hour day-of-month month day-of-week.
+// Each field takes `*`, numbers, ranges (`1-5`), steps (`*/15`, `0-30/10`)
+// and comma-separated lists of those.
+#[derive(Clone)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    // Cron matches either day field when both are restricted
+    day_of_month_any: bool,
+    day_of_week_any: bool,
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("Invalid step in {part:?}"))?;
+                if step == 0 {
+                    return Err(format!("Invalid step in {part:?}"));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            let start = start
+                .parse()
+                .map_err(|_| format!("Invalid range {part:?}"))?;
+            let end = end.parse().map_err(|_| format!("Invalid range {part:?}"))?;
+            (start, end)
+        } else {
+            let value = range
+                .parse()
+                .map_err(|_| format!("Invalid value {part:?}"))?;
+            // `5/10` means starting at 5, every 10
+            if step > 1 {
+                (value, max)
+            } else {
+                (value, value)
+            }
+        };
+
+        if start < min || end > max || start > end {
+            return Err(format!("{part:?} is outside {min}-{max}"));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+impl CronExpr {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(format!("Expected 5 fields, got {}", fields.len()));
+        };
+
+        let mut days_of_week = parse_field(day_of_week, 0, 7)?;
+        // 7 is Sunday too
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1;
+        }
+
+        Ok(CronExpr {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days_of_month: parse_field(day_of_month, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            days_of_week,
+            day_of_month_any: day_of_month == "*",
+            day_of_week_any: day_of_week == "*",
+        })
+    }
+
+    pub fn matches(&self, time: &DateTime<Local>) -> bool {
+        let has = |bits: u64, value: u32| bits & (1 << value) != 0;
+
+        let day_of_month = has(self.days_of_month, time.day());
+        let day_of_week = has(self.days_of_week, time.weekday().num_days_from_sunday());
+        let day = match (self.day_of_month_any, self.day_of_week_any) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+
+        has(self.minutes, time.minute())
+            && has(self.hours, time.hour())
+            && has(self.months, time.month())
+            && day
+    }
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScheduleAction {
+    // Opens the panel, optionally showing a prompt to write to
+    Prompt { text: Option<String> },
+    // Saves a thought with the given text without any interaction
+    Capture { text: String },
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub struct ScheduleEntry {
+    id: String,
+    cron: String,
+    action: ScheduleAction,
+}
+
+// Schedule entries persisted as JSON in the config dir
+pub struct Schedules {
+    path: PathBuf,
+    entries: Mutex<Vec<(ScheduleEntry, CronExpr)>>,
+}
+
+impl Schedules {
+    pub fn load(path: PathBuf) -> Self {
+        let entries: Vec<ScheduleEntry> = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        let entries = entries
+            .into_iter()
+            .filter_map(|entry| match CronExpr::parse(&entry.cron) {
+                Ok(cron) => Some((entry, cron)),
+                Err(e) => {
+                    log_error!("Skipping schedule {}: {e}", entry.id);
+                    None
+                }
+            })
+            .collect();
+
+        Schedules {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn save(&self, entries: &[(ScheduleEntry, CronExpr)]) -> io::Result<()> {
+        let entries: Vec<&ScheduleEntry> = entries.iter().map(|(entry, _)| entry).collect();
+        write_atomic(&self.path, &serde_json::to_string_pretty(&entries)?)
+    }
+
+    fn due(&self, time: &DateTime<Local>) -> Vec<ScheduleAction> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, cron)| cron.matches(time))
+            .map(|(entry, _)| entry.action.clone())
+            .collect()
+    }
+}
+
+fn run_action(app: &AppHandle, action: ScheduleAction) {
+    match action {
+        ScheduleAction::Prompt { text } => {
+            let handle = app.clone();
+            let _ = app.run_on_main_thread(move || {
+                if let Some(window) = handle.get_webview_window("quick-panel") {
+                    let _ = window.show();
+                    let _ = window.set_focus();
+                }
+                let _ = handle.emit_to("quick-panel", "scheduled-prompt", text);
+            });
+        }
+        ScheduleAction::Capture { text } => {
+            // The panel owns thought creation, so it does the actual save
+            let _ = app.emit_to("quick-panel", "scheduled-capture", text);
+        }
+    }
+}
+
+// Only the current minute is ever checked, so triggers missed while the app
+// was closed or the machine asleep are dropped rather than fired in a burst
+pub fn start(app: AppHandle) {
+    thread::spawn(move || {
+        let minute_of = |time: &DateTime<Local>| time.timestamp() / 60;
+        let mut last_checked = minute_of(&Local::now());
+
+        loop {
+            thread::sleep(TICK_INTERVAL);
+
+            let now = Local::now();
+            let minute = minute_of(&now);
+            if minute == last_checked {
+                continue;
+            }
+            last_checked = minute;
+
+            let Some(state) = app.try_state::<AppState>() else {
+                continue;
+            };
+            for action in state.schedules.due(&now) {
+                run_action(&app, action);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn add_schedule(
+    state: tauri::State<AppState>,
+    cron: String,
+    action: ScheduleAction,
+) -> Result<ScheduleEntry, String> {
+    let expr = CronExpr::parse(&cron)?;
+    let entry = ScheduleEntry {
+        id: uuid::Uuid::new_v4().to_string(),
+        cron,
+        action,
+    };
+
+    let mut entries = state.schedules.entries.lock().unwrap();
+    entries.push((entry.clone(), expr));
+    if let Err(e) = state.schedules.save(&entries) {
+        entries.pop();
+        return Err(e.to_string());
+    }
+    Ok(entry)
+}
+
+#[tauri::command]
+pub fn list_schedules(state: tauri::State<AppState>) -> Vec<ScheduleEntry> {
+    state
+        .schedules
+        .entries
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(entry, _)| entry.clone())
+        .collect()
+}
+
+// Returns false when no schedule has the given id
+#[tauri::command]
+pub fn remove_schedule(state: tauri::State<AppState>, id: String) -> Result<bool, String> {
+    let mut entries = state.schedules.entries.lock().unwrap();
+    let Some(index) = entries.iter().position(|(entry, _)| entry.id == id) else {
+        return Ok(false);
+    };
+
+    let removed = entries.remove(index);
+    if let Err(e) = state.schedules.save(&entries) {
+        entries.insert(index, removed);
+        return Err(e.to_string());
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn cron(expr: &str) -> CronExpr {
+        CronExpr::parse(expr).unwrap()
+    }
+
+    #[test]
+    fn step_from_a_value_starts_there() {
+        let expr = cron("5/10 * * * *");
+        assert!(expr.matches(&at(2024, 6, 3, 9, 5)));
+        assert!(expr.matches(&at(2024, 6, 3, 9, 55)));
+        assert!(!expr.matches(&at(2024, 6, 3, 9, 0)));
+        assert!(!expr.matches(&at(2024, 6, 3, 9, 10)));
+    }
+
+    #[test]
+    fn steps_ranges_and_lists() {
+        let expr = cron("*/15 9-17 * * 1-5");
+        assert!(expr.matches(&at(2024, 6, 3, 9, 45)));
+        assert!(!expr.matches(&at(2024, 6, 3, 18, 0)));
+        assert!(!expr.matches(&at(2024, 6, 3, 9, 20)));
+        // Saturday
+        assert!(!expr.matches(&at(2024, 6, 15, 9, 0)));
+
+        let expr = cron("0,30 8,20 * * *");
+        assert!(expr.matches(&at(2024, 6, 3, 20, 30)));
+        assert!(!expr.matches(&at(2024, 6, 3, 12, 30)));
+    }
+
+    #[test]
+    fn seven_is_sunday() {
+        let expr = cron("0 9 * * 7");
+        assert!(expr.matches(&at(2024, 6, 2, 9, 0)));
+        assert!(!expr.matches(&at(2024, 6, 3, 9, 0)));
+        assert_eq!(expr.days_of_week, cron("0 9 * * 0").days_of_week | 1 << 7);
+    }
+
+    #[test]
+    fn either_day_field_matches_when_both_are_restricted() {
+        // The 1st of the month or any Monday
+        let expr = cron("0 9 1 * 1");
+        assert!(expr.matches(&at(2024, 6, 1, 9, 0)));
+        assert!(expr.matches(&at(2024, 6, 3, 9, 0)));
+        assert!(!expr.matches(&at(2024, 6, 4, 9, 0)));
+    }
+
+    #[test]
+    fn both_day_fields_must_match_when_one_is_any() {
+        let expr = cron("0 9 1 * *");
+        assert!(expr.matches(&at(2024, 6, 1, 9, 0)));
+        assert!(!expr.matches(&at(2024, 6, 3, 9, 0)));
+
+        let expr = cron("0 9 * 6 1");
+        assert!(expr.matches(&at(2024, 6, 3, 9, 0)));
+        assert!(!expr.matches(&at(2024, 7, 1, 8, 0)));
+        assert!(!expr.matches(&at(2024, 7, 1, 9, 0)));
+    }
+
+    #[test]
+    fn rejects_zero_step() {
+        assert!(CronExpr::parse("*/0 * * * *").is_err());
+        assert!(CronExpr::parse("5/0 * * * *").is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        for expr in [
+            "60 * * * *",
+            "0 24 * * *",
+            "0 0 0 * *",
+            "0 0 32 * *",
+            "0 0 * 13 *",
+            "0 0 * * 8",
+            "30-10 * * * *",
+        ] {
+            assert!(CronExpr::parse(expr).is_err(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_fields() {
+        assert!(CronExpr::parse("a * * * *").is_err());
+        assert!(CronExpr::parse("1-x * * * *").is_err());
+        assert!(CronExpr::parse("*/x * * * *").is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_field_count() {
+        assert_eq!(
+            CronExpr::parse("* * * *").err().as_deref(),
+            Some("Expected 5 fields, got 4")
+        );
+        assert!(CronExpr::parse("* * * * * *").is_err());
+        assert!(CronExpr::parse("").is_err());
+    }
+}
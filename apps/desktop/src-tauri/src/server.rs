@@ -0,0 +1,767 @@
+use colored::Colorize;
+use std::fs;
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::async_runtime::Receiver;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use tauri_plugin_shell::ShellExt;
+
+use crate::config::Config;
+use crate::logging::{self, Level};
+use crate::AppState;
+
+// Spawns the tRPC server, either the bundled sidecar or an alternative build
+// from the config dir's `servers/` folder, and pumps its output to the terminal.
+// The port and data dir are passed as arguments so the server never picks its
+// own; the env vars are kept for builds that predate the flags.
+#[tracing::instrument(skip(app, config), fields(port = config.get_port()))]
+pub fn spawn_server(
+    app: &AppHandle,
+    config: &Config,
+    binary: Option<&Path>,
+) -> Result<CommandChild, String> {
+    let command = match binary {
+        Some(path) => app.shell().command(path),
+        None => app.shell().sidecar("server").map_err(|e| e.to_string())?,
+    };
+
+    let port = config.get_port().to_string();
+    let (rx, child) = command
+        .args(["--port", &port])
+        .arg("--data-dir")
+        .arg(config.get_data_dir())
+        .env("SIDECAR_PORT", &port)
+        .env("THOUGHTS_CONFIG_PATH", config.get_data_dir())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    pipe_output(app.clone(), child.pid(), rx);
+    tracing::info!(pid = child.pid(), "server started");
+    Ok(child)
+}
+
+const SIDECAR_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+// Dev only: when the app started without a server because the sidecar hadn't
+// been built, keeps trying until the binary shows up
+pub fn attach_when_available(app: AppHandle) {
+    thread::spawn(move || loop {
+        let state = app.state::<AppState>();
+        let mut server = state.server.lock().unwrap();
+        if server.is_some() {
+            return;
+        }
+
+        if let Ok(child) = spawn_server(&app, &state.config, None) {
+            if let Err(e) = state.config.write_pid_file(child.pid()) {
+                log_error!("Failed to write PID file: {e}");
+            }
+            *server = Some(child);
+            tracing::info!("sidecar found, server attached");
+            return;
+        }
+
+        drop(server);
+        thread::sleep(SIDECAR_RETRY_INTERVAL);
+    });
+}
+
+// Offers Retry or Quit for an error the app can't continue past. Blocks until
+// answered and returns true for Retry. Where there's no dialog to show, the
+// answer is always Quit.
+pub fn ask_retry(message: &str) -> bool {
+    log_error!("{message}");
+
+    #[cfg(target_os = "macos")]
+    {
+        let escaped = message.replace('\\', "\\\\").replace('"', "\\\"");
+        std::process::Command::new("osascript")
+            .arg("-e")
+            .arg(format!(
+                "display alert \"Thoughts couldn't start\" message \"{escaped}\" as critical buttons {{\"Quit\", \"Retry\"}} default button \"Retry\""
+            ))
+            .output()
+            .is_ok_and(|output| {
+                String::from_utf8_lossy(&output.stdout).contains("button returned:Retry")
+            })
+    }
+    #[cfg(not(target_os = "macos"))]
+    false
+}
+
+// Blocks until dismissed
+pub fn show_alert(title: &str, message: &str) {
+    #[cfg(target_os = "macos")]
+    {
+        let escaped = message.replace('\\', "\\\\").replace('"', "\\\"");
+        let _ = std::process::Command::new("osascript")
+            .arg("-e")
+            .arg(format!(
+                "display alert \"{title}\" message \"{escaped}\" as critical"
+            ))
+            .status();
+    }
+    #[cfg(not(target_os = "macos"))]
+    let _ = (title, message);
+}
+
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Blocks until the server accepts connections on `port`. Emits `server-ready`
+// once it does; after READY_TIMEOUT it alerts the user and returns false.
+pub fn wait_until_ready(app: &AppHandle, port: u16) -> bool {
+    let deadline = Instant::now() + READY_TIMEOUT;
+    while !is_listening(port) {
+        if Instant::now() >= deadline {
+            let message = format!(
+                "The Thoughts server didn't start listening on port {port} within {}s, so the shortcut is disabled. Restart the app to try again.",
+                READY_TIMEOUT.as_secs()
+            );
+            log_error!("{message}");
+            show_alert("Thoughts server isn't responding", &message);
+            return false;
+        }
+        thread::sleep(READY_POLL_INTERVAL);
+    }
+
+    tracing::info!(port, "server ready");
+    let _ = app.emit("server-ready", port);
+    true
+}
+
+// The build the server was last started from, None for the bundled sidecar
+pub fn active_build_path(state: &AppState) -> Option<PathBuf> {
+    let build = state.server_build.lock().unwrap().clone();
+    build.map(|name| state.config.get_servers_dir().join(name))
+}
+
+// Crash restarts back off from 1s, doubling up to this
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_RESTARTS: u32 = 5;
+// A server that stayed up this long resets the restart count
+const STABLE_UPTIME: Duration = Duration::from_secs(60);
+
+static RESTART_ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Clone, serde::Serialize)]
+struct ServerRestart {
+    attempt: u32,
+    pid: u32,
+    exit_code: Option<i32>,
+}
+
+// Called when a server process exits. Everything that stops the server on
+// purpose takes it out of the state first, so an exit of the server still in
+// the state is a crash.
+async fn restart_after_crash(app: AppHandle, pid: u32, uptime: Duration, exit_code: Option<i32>) {
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    {
+        let mut server = state.server.lock().unwrap();
+        if server.as_ref().map(|child| child.pid()) != Some(pid) {
+            return;
+        }
+        *server = None;
+    }
+    state.config.cleanup_pid_file();
+
+    if uptime >= STABLE_UPTIME {
+        RESTART_ATTEMPTS.store(0, Ordering::SeqCst);
+    }
+    loop {
+        let attempt = RESTART_ATTEMPTS.fetch_add(1, Ordering::SeqCst) + 1;
+        if attempt > MAX_RESTARTS {
+            log_error!("Server keeps exiting, giving up after {MAX_RESTARTS} restarts");
+            let _ = app.emit("server-restart-failed", exit_code);
+            return;
+        }
+
+        let delay = Duration::from_secs(1 << (attempt - 1)).min(MAX_RESTART_BACKOFF);
+        log_error!("Server exited unexpectedly (code {exit_code:?}), restarting in {delay:?}");
+        tokio::time::sleep(delay).await;
+
+        let mut server = state.server.lock().unwrap();
+        // Started by something else while we waited
+        if server.is_some() {
+            return;
+        }
+        let binary = active_build_path(&state);
+        match spawn_server(&app, &state.config, binary.as_deref()) {
+            Ok(child) => {
+                if let Err(e) = state.config.write_pid_file(child.pid()) {
+                    log_error!("Failed to write PID file: {e}");
+                }
+                tracing::info!(attempt, pid = child.pid(), "server restarted after a crash");
+                let _ = app.emit(
+                    "server-restarted",
+                    ServerRestart {
+                        attempt,
+                        pid: child.pid(),
+                        exit_code,
+                    },
+                );
+                *server = Some(child);
+                return;
+            }
+            Err(e) => log_error!("Failed to restart server: {e}"),
+        }
+    }
+}
+
+// A line of server output, emitted as `server-log` for in-app log viewing
+#[derive(Clone, serde::Serialize)]
+struct ServerLog {
+    stream: &'static str,
+    line: String,
+}
+
+fn emit_server_log(app: &AppHandle, stream: &'static str, line: &[u8]) -> String {
+    let line = String::from_utf8_lossy(line)
+        .trim_end_matches(['\r', '\n'])
+        .to_string();
+    let _ = app.emit(
+        "server-log",
+        ServerLog {
+            stream,
+            line: line.clone(),
+        },
+    );
+    line
+}
+
+fn pipe_output(app: AppHandle, pid: u32, mut rx: Receiver<CommandEvent>) {
+    tauri::async_runtime::spawn(async move {
+        let started = Instant::now();
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Stdout(line) => {
+                    let line = emit_server_log(&app, "stdout", &line);
+                    tracing::info!(pid, stream = "stdout", line = %line, "server output");
+                    if cfg!(debug_assertions) {
+                        println!("{} {}", "[tRPC]".bright_blue().bold(), line);
+                    }
+                    logging::mirror("server", Level::Info, &line);
+                }
+                CommandEvent::Stderr(line) => {
+                    let line = emit_server_log(&app, "stderr", &line);
+                    tracing::warn!(pid, stream = "stderr", line = %line, "server output");
+                    if cfg!(debug_assertions) {
+                        println!("{} {}", "[tRPC]".bright_red().bold(), line);
+                    }
+                    logging::mirror("server", Level::Error, &line);
+                }
+                CommandEvent::Terminated(payload) => {
+                    tracing::info!(pid, code = ?payload.code, "server exited");
+                    restart_after_crash(app.clone(), pid, started.elapsed(), payload.code).await;
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+#[derive(serde::Serialize)]
+pub struct ServerBuild {
+    name: String,
+    path: PathBuf,
+    active: bool,
+}
+
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.is_file()
+    }
+}
+
+#[tauri::command]
+pub fn list_server_builds(state: tauri::State<AppState>) -> Result<Vec<ServerBuild>, String> {
+    let servers_dir = state.config.get_servers_dir();
+    let entries = match fs::read_dir(&servers_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let active = state.server_build.lock().unwrap().clone();
+    let mut builds: Vec<ServerBuild> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_executable(path))
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            Some(ServerBuild {
+                active: active.as_deref() == Some(name.as_str()),
+                name,
+                path,
+            })
+        })
+        .collect();
+    builds.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(builds)
+}
+
+// Switches to a named build from `servers/`, or back to the bundled sidecar
+// when `name` is None. The old server is stopped and gone before the new one
+// starts, so they never compete for the port.
+#[tauri::command]
+pub async fn switch_server_build(app: AppHandle, name: Option<String>) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        swap_server_build(&app, &state, name)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn swap_server_build(
+    app: &AppHandle,
+    state: &AppState,
+    name: Option<String>,
+) -> Result<(), String> {
+    let binary = match &name {
+        Some(name) => {
+            let path = state.config.get_servers_dir().join(name);
+            // Reject anything that would escape the servers dir
+            if path.parent() != Some(state.config.get_servers_dir().as_path()) {
+                return Err(format!("Invalid server build name: {name}"));
+            }
+            if !is_executable(&path) {
+                return Err(format!("{} is not an executable file", path.display()));
+            }
+            Some(path)
+        }
+        None => None,
+    };
+
+    // Holding the lock for the whole swap keeps concurrent switches from
+    // leaving two servers running
+    let _span = tracing::info_span!("switch_server_build", name = ?name).entered();
+    let mut server = state.server.lock().unwrap();
+    if let Some(child) = server.take() {
+        tracing::info!(pid = child.pid(), "stopping server");
+        let timeout = Duration::from_millis(state.config.get_shutdown_timeout_ms());
+        state
+            .config
+            .terminate_server_gracefully(child.pid(), timeout);
+    }
+
+    let child = spawn_server(app, &state.config, binary.as_deref())?;
+    if let Err(e) = state.config.write_pid_file(child.pid()) {
+        log_error!("Failed to write PID file: {e}");
+    }
+    *server = Some(child);
+    *state.server_build.lock().unwrap() = name;
+
+    Ok(())
+}
+
+// Stops the server and starts the same build again, for when it's stuck. The
+// server lock is held throughout, so concurrent restarts run one after the
+// other instead of leaving two servers behind.
+#[tauri::command]
+pub async fn restart_server(app: AppHandle) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<AppState>();
+        let _span = tracing::info_span!("restart_server").entered();
+        let mut server = state.server.lock().unwrap();
+        if let Some(child) = server.take() {
+            tracing::info!(pid = child.pid(), "stopping server for restart");
+            let timeout = Duration::from_millis(state.config.get_shutdown_timeout_ms());
+            state
+                .config
+                .terminate_server_gracefully(child.pid(), timeout);
+        }
+
+        let binary = active_build_path(&state);
+        let child = spawn_server(&app, &state.config, binary.as_deref())?;
+        if let Err(e) = state.config.write_pid_file(child.pid()) {
+            log_error!("Failed to write PID file: {e}");
+        }
+        *server = Some(child);
+        // A deliberate restart starts the crash backoff over
+        RESTART_ATTEMPTS.store(0, Ordering::SeqCst);
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Whether the server this app spawned is still running. Only signals the
+// stored pid, so it's cheap enough for the frontend to poll, e.g. after a wake,
+// and call restart_server when it's gone.
+#[tauri::command]
+pub fn is_server_alive(state: tauri::State<AppState>) -> bool {
+    let server = state.server.lock().unwrap();
+    server
+        .as_ref()
+        .is_some_and(|child| process_alive(child.pid()))
+}
+
+// Writes a line to the server's stdin. Around system sleep that's `quiesce`,
+// asking it to flush and stop writing, then `resume`.
+#[cfg(target_os = "macos")]
+pub fn send_server_command(state: &AppState, command: &str) {
+    let mut server = state.server.lock().unwrap();
+    let Some(child) = server.as_mut() else {
+        return;
+    };
+    if let Err(e) = child.write(format!("{command}\n").as_bytes()) {
+        log_error!("Failed to send {command} to the server: {e}");
+    }
+}
+
+// kill with signal 0 only checks the process exists; EPERM means it does but
+// belongs to someone else
+#[cfg(unix)]
+pub fn process_alive(pid: u32) -> bool {
+    let result = unsafe { libc::kill(pid as i32, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+pub fn process_alive(pid: u32) -> bool {
+    use windows::Win32::Foundation::{CloseHandle, STILL_ACTIVE};
+    use windows::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    unsafe {
+        let Ok(handle) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) else {
+            return false;
+        };
+        let mut exit_code = 0;
+        let alive = GetExitCodeProcess(handle, &mut exit_code).is_ok()
+            && exit_code == STILL_ACTIVE.0 as u32;
+        let _ = CloseHandle(handle);
+        alive
+    }
+}
+
+// Asks the process to exit; the server shuts down cleanly on SIGTERM
+#[cfg(unix)]
+pub fn terminate_process(pid: u32) {
+    unsafe {
+        libc::kill(pid as i32, libc::SIGTERM);
+    }
+}
+
+// For a process that ignored terminate_process
+#[cfg(unix)]
+pub fn kill_process(pid: u32) {
+    unsafe {
+        libc::kill(pid as i32, libc::SIGKILL);
+    }
+}
+
+// Windows has no SIGTERM for a console-less process, so it's ended outright
+#[cfg(windows)]
+pub fn terminate_process(pid: u32) {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{OpenProcess, TerminateProcess, PROCESS_TERMINATE};
+
+    unsafe {
+        if let Ok(handle) = OpenProcess(PROCESS_TERMINATE, false, pid) {
+            let _ = TerminateProcess(handle, 1);
+            let _ = CloseHandle(handle);
+        }
+    }
+}
+
+#[cfg(windows)]
+pub fn kill_process(pid: u32) {
+    terminate_process(pid);
+}
+
+// Full path of a process's executable. Unlike the command name this can't be
+// spoofed by the process or truncated.
+#[cfg(target_os = "macos")]
+fn process_path(pid: u32) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+
+    let mut buffer = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    let len =
+        unsafe { libc::proc_pidpath(pid as i32, buffer.as_mut_ptr().cast(), buffer.len() as u32) };
+    if len <= 0 {
+        return None;
+    }
+    buffer.truncate(len as usize);
+    Some(PathBuf::from(std::ffi::OsString::from_vec(buffer)))
+}
+
+#[cfg(target_os = "linux")]
+fn process_path(pid: u32) -> Option<PathBuf> {
+    fs::read_link(format!("/proc/{pid}/exe")).ok()
+}
+
+#[cfg(windows)]
+fn process_path(pid: u32) -> Option<PathBuf> {
+    use windows::core::PWSTR;
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    let mut buffer = [0u16; 1024];
+    let mut len = buffer.len() as u32;
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let result = QueryFullProcessImageNameW(
+            handle,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &mut len,
+        );
+        let _ = CloseHandle(handle);
+        result.ok()?;
+    }
+    Some(PathBuf::from(String::from_utf16_lossy(
+        &buffer[..len as usize],
+    )))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", windows)))]
+fn process_path(_pid: u32) -> Option<PathBuf> {
+    None
+}
+
+// Where Tauri puts the bundled sidecar, next to the app's own executable
+fn sidecar_path() -> Option<PathBuf> {
+    let name = if cfg!(windows) {
+        "server.exe"
+    } else {
+        "server"
+    };
+    let exe = std::env::current_exe().ok()?;
+    exe.parent()?.join(name).canonicalize().ok()
+}
+
+// Our server is either the bundled sidecar or one of the builds in the config
+// dir's `servers/` folder, compared by their resolved paths so another app's
+// `server` binary doesn't pass. A process whose path can't be read is assumed
+// not to be ours, so it's never signalled.
+fn is_our_server(config: &Config, pid: u32) -> bool {
+    let Some(path) = process_path(pid).and_then(|path| path.canonicalize().ok()) else {
+        return false;
+    };
+    sidecar_path().is_some_and(|sidecar| path == sidecar)
+        || config
+            .get_servers_dir()
+            .canonicalize()
+            .is_ok_and(|servers_dir| path.starts_with(servers_dir))
+}
+
+fn is_listening(port: u16) -> bool {
+    TcpStream::connect_timeout(
+        &SocketAddr::from(([127, 0, 0, 1], port)),
+        Duration::from_millis(200),
+    )
+    .is_ok()
+}
+
+// Cross-checks every server PID file in the config dir against the running
+// processes and the port they name, fixing what a crash left behind. `running`
+// is the PID of the server this instance owns, if any. Returns a description
+// of each fix, which is also logged.
+pub fn reconcile_pid_files(config: &Config, running: Option<u32>) -> Vec<String> {
+    let mut fixes = Vec::new();
+    let port = config.get_port();
+
+    let entries = fs::read_dir(config.get_config_dir()).into_iter().flatten();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some(file_port) = name
+            .strip_prefix("server-")
+            .and_then(|rest| rest.strip_suffix(".pid"))
+            .and_then(|value| value.parse::<u16>().ok())
+        else {
+            continue;
+        };
+        let path = entry.path();
+        let pid = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| content.trim().parse::<u32>().ok());
+
+        let fix = match pid {
+            Some(pid) if Some(pid) == running && file_port == port => continue,
+            None => format!("removed unreadable {name}"),
+            Some(pid) if !process_alive(pid) => {
+                format!("removed {name}, process {pid} is no longer running")
+            }
+            Some(pid) if !is_our_server(config, pid) => {
+                let path = process_path(pid).map(|path| path.display().to_string());
+                format!(
+                    "removed {name} without signalling process {pid} ({}), it is not our server",
+                    path.as_deref().unwrap_or("unknown executable")
+                )
+            }
+            Some(pid) if Some(pid) == running => {
+                format!("removed {name}, our server is on port {port}")
+            }
+            Some(pid) => {
+                let timeout = Duration::from_millis(config.get_shutdown_timeout_ms());
+                if config.terminate_server_gracefully(pid, timeout) {
+                    format!("stopped orphaned server {pid} on port {file_port}")
+                } else {
+                    format!("killed orphaned server {pid} on port {file_port}, it ignored SIGTERM")
+                }
+            }
+        };
+        let _ = fs::remove_file(&path);
+        fixes.push(fix);
+    }
+
+    if let Some(pid) = running {
+        if config.read_pid_file() != Some(pid) {
+            match config.write_pid_file(pid) {
+                Ok(()) => fixes.push(format!("rewrote the PID file for server {pid}")),
+                Err(e) => log_error!("Failed to write PID file: {e}"),
+            }
+        }
+        if !is_listening(port) {
+            fixes.push(format!("server {pid} is not listening on port {port}"));
+        }
+    } else if is_listening(port) {
+        // Nothing of ours should hold the port before the server is spawned
+        fixes.push(format!("port {port} is in use by another process"));
+    }
+
+    for fix in &fixes {
+        tracing::warn!(fix = %fix, "reconciled server state");
+    }
+    fixes
+}
+
+#[tauri::command]
+pub fn reconcile_server_state(state: tauri::State<AppState>) -> Vec<String> {
+    let running = state
+        .server
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|child| child.pid());
+    reconcile_pid_files(&state.config, running)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{unused_port, TempDir};
+    use std::process::Command;
+
+    #[test]
+    fn removes_unreadable_pid_file() {
+        let dir = TempDir::new();
+        let config = Config::for_test(dir.path(), unused_port());
+        let stale = dir.path().join("server-4400.pid");
+        fs::write(&stale, "not a pid").unwrap();
+
+        let fixes = reconcile_pid_files(&config, None);
+
+        assert_eq!(fixes, ["removed unreadable server-4400.pid"]);
+        assert!(!stale.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn removes_pid_file_of_dead_process() {
+        let dir = TempDir::new();
+        let config = Config::for_test(dir.path(), unused_port());
+        let mut child = Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        let stale = dir.path().join("server-4401.pid");
+        fs::write(&stale, pid.to_string()).unwrap();
+
+        let fixes = reconcile_pid_files(&config, None);
+
+        assert_eq!(
+            fixes,
+            [format!(
+                "removed server-4401.pid, process {pid} is no longer running"
+            )]
+        );
+        assert!(!stale.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn leaves_live_foreign_process_running() {
+        let dir = TempDir::new();
+        let config = Config::for_test(dir.path(), unused_port());
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id();
+        let stale = dir.path().join("server-4402.pid");
+        fs::write(&stale, pid.to_string()).unwrap();
+
+        let fixes = reconcile_pid_files(&config, None);
+        let alive = process_alive(pid);
+        let _ = child.kill();
+        let _ = child.wait();
+
+        assert_eq!(fixes.len(), 1);
+        assert!(fixes[0].contains("it is not our server"), "{}", fixes[0]);
+        assert!(alive);
+        assert!(!stale.exists());
+    }
+
+    #[cfg(unix)]
+    use crate::test_support::spawn_copy_of_sleep;
+
+    #[cfg(unix)]
+    #[test]
+    fn recognises_builds_in_servers_dir() {
+        let dir = TempDir::new();
+        let config = Config::for_test(dir.path(), unused_port());
+        let mut child = spawn_copy_of_sleep(&config.get_servers_dir().join("v2").join("server"));
+
+        let ours = is_our_server(&config, child.id());
+        let _ = child.kill();
+        let _ = child.wait();
+
+        assert!(ours);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_other_binaries_named_server() {
+        let dir = TempDir::new();
+        let config = Config::for_test(dir.path(), unused_port());
+        let mut child = spawn_copy_of_sleep(&dir.path().join("elsewhere").join("server"));
+
+        let ours = is_our_server(&config, child.id());
+        let _ = child.kill();
+        let _ = child.wait();
+
+        assert!(!ours);
+    }
+
+    #[test]
+    fn ignores_files_that_are_not_pid_files() {
+        let dir = TempDir::new();
+        let config = Config::for_test(dir.path(), unused_port());
+        fs::write(dir.path().join("server-x.pid"), "1").unwrap();
+        fs::write(dir.path().join("config.toml"), "").unwrap();
+
+        assert!(reconcile_pid_files(&config, None).is_empty());
+        assert!(dir.path().join("server-x.pid").exists());
+    }
+}
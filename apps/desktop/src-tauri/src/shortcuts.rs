@@ -0,0 +1,585 @@
+use std::collections::HashMap;
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tauri_plugin_global_shortcut::{
+    GlobalShortcut, GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState,
+};
+
+use crate::config::write_atomic;
+use crate::{apply_privacy_blur, flip_record_mode, open_main_window, toggle_launchbar, AppState};
+
+// Things a global shortcut can be bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortcutAction {
+    TogglePanel,
+    TogglePrivacyBlur,
+    OpenMainWindow,
+    ToggleRecordMode,
+}
+
+impl ShortcutAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ShortcutAction::TogglePanel => "toggle_panel",
+            ShortcutAction::TogglePrivacyBlur => "toggle_privacy_blur",
+            ShortcutAction::OpenMainWindow => "open_main_window",
+            ShortcutAction::ToggleRecordMode => "toggle_record_mode",
+        }
+    }
+}
+
+// Which action each registered shortcut triggers. The global shortcut handler
+// dispatches through this, so rebinding never needs a new handler.
+pub struct ShortcutBindings {
+    bindings: Mutex<HashMap<ShortcutAction, Shortcut>>,
+    // When each action last went through, for debouncing key repeat and
+    // double taps
+    last_pressed: Mutex<HashMap<ShortcutAction, Instant>>,
+    debounce: Duration,
+}
+
+impl ShortcutBindings {
+    pub fn new(debounce_ms: u64) -> Self {
+        ShortcutBindings {
+            bindings: Mutex::new(HashMap::new()),
+            last_pressed: Mutex::new(HashMap::new()),
+            debounce: Duration::from_millis(debounce_ms),
+        }
+    }
+
+    pub fn register(
+        &self,
+        app: &AppHandle,
+        action: ShortcutAction,
+        shortcut: Shortcut,
+    ) -> Result<(), String> {
+        let mut bindings = self.bindings.lock().unwrap();
+        app.global_shortcut()
+            .register(shortcut)
+            .map_err(|e| e.to_string())?;
+        if let Some(previous) = bindings.insert(action, shortcut) {
+            if previous != shortcut {
+                let _ = app.global_shortcut().unregister(previous);
+            }
+        }
+        tracing::info!(action = action.as_str(), shortcut = %shortcut, "shortcut registered");
+        Ok(())
+    }
+
+    fn action_for(&self, shortcut: &Shortcut) -> Option<ShortcutAction> {
+        self.bindings
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, bound)| *bound == shortcut)
+            .map(|(action, _)| *action)
+    }
+
+    // False when the action already fired within the debounce window. Ignored
+    // presses don't extend the window, so holding the keys down still lets a
+    // deliberate second press through once it's over.
+    fn accept_press(&self, action: ShortcutAction) -> bool {
+        let now = Instant::now();
+        let mut last_pressed = self.last_pressed.lock().unwrap();
+        if let Some(last) = last_pressed.get(&action) {
+            if now.duration_since(*last) < self.debounce {
+                return false;
+            }
+        }
+        last_pressed.insert(action, now);
+        true
+    }
+}
+
+// Runs inside the event loop, where a panic would take the whole app down, so
+// one is caught and logged instead
+pub fn handle_shortcut(app: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        dispatch_shortcut(app, shortcut, event)
+    }));
+    if result.is_err() {
+        log_error!("Shortcut handler panicked for {shortcut}");
+    }
+}
+
+fn dispatch_shortcut(app: &AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let Some(action) = state.shortcuts.action_for(shortcut) else {
+        return;
+    };
+
+    // Only presses are debounced so releases always arrive paired with the
+    // press that went through
+    match event.state() {
+        ShortcutState::Pressed => {
+            if !state.shortcuts.accept_press(action) {
+                return;
+            }
+        }
+        ShortcutState::Released => return,
+    }
+    state.metrics.count_shortcut(action);
+
+    match action {
+        ShortcutAction::TogglePanel => toggle_launchbar(app),
+        ShortcutAction::TogglePrivacyBlur => {
+            let enabled = state.privacy_blur.load(Ordering::SeqCst);
+            apply_privacy_blur(app, !enabled);
+        }
+        ShortcutAction::OpenMainWindow => {
+            if let Err(e) = open_main_window(app.clone()) {
+                log_error!("Failed to open the main window: {e}");
+            }
+        }
+        ShortcutAction::ToggleRecordMode => {
+            flip_record_mode(app, None);
+        }
+    }
+}
+
+// Tells the user the panel shortcut is unavailable, since otherwise nothing
+// happens when they press it
+pub fn report_registration_failure(app: &AppHandle, shortcut: &str, reason: &str) {
+    log_error!("Failed to register shortcut {shortcut}: {reason}");
+    let _ = app.emit(
+        "shortcut-registration-failed",
+        ShortcutBindingError {
+            action: ShortcutAction::TogglePanel,
+            shortcut: shortcut.to_owned(),
+            reason: reason.to_owned(),
+        },
+    );
+    #[cfg(target_os = "macos")]
+    {
+        let message = format!(
+            "{shortcut} is probably used by another app. Set THOUGHTS_SHORTCUT to pick a different one."
+        );
+        let escaped = message.replace('\\', "\\\\").replace('"', "\\\"");
+        std::thread::spawn(move || {
+            let _ = Command::new("osascript")
+                .arg("-e")
+                .arg(format!(
+                    "display alert \"Couldn't register the Thoughts shortcut\" message \"{escaped}\""
+                ))
+                .status();
+        });
+    }
+}
+
+// Rebinds the panel shortcut until the app restarts
+#[tauri::command]
+pub fn set_shortcut(
+    app: AppHandle,
+    state: tauri::State<AppState>,
+    shortcut: String,
+) -> Result<(), String> {
+    rebind_panel_shortcut(&app, &state, &shortcut, false).map_err(|e| e.to_string())
+}
+
+// Why try_register_shortcut didn't take, e.g.
+// `{ "kind": "conflict", "detail": "..." }`
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum ShortcutError {
+    // The string doesn't parse as a shortcut
+    Invalid(String),
+    // Already bound to another of the app's actions
+    InUse(ShortcutAction),
+    // The OS refused it, usually because another app holds the combo
+    Conflict(String),
+    // Registered, but couldn't be saved for the next launch
+    NotSaved(String),
+}
+
+impl std::fmt::Display for ShortcutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShortcutError::Invalid(message) => write!(f, "Invalid shortcut: {message}"),
+            ShortcutError::InUse(action) => write!(f, "Already bound to {}", action.as_str()),
+            ShortcutError::Conflict(message) => write!(f, "Couldn't register: {message}"),
+            ShortcutError::NotSaved(message) => write!(f, "Couldn't save the shortcut: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ShortcutError {}
+
+fn parse_shortcut(shortcut: &str) -> Result<Shortcut, ShortcutError> {
+    Shortcut::from_str(shortcut).map_err(|e| ShortcutError::Invalid(e.to_string()))
+}
+
+// Rebinds the panel shortcut and keeps it for later launches, where it wins
+// over config.toml but not THOUGHTS_SHORTCUT. The new combo is registered
+// before the old one is released, so on any failure the old one still works.
+#[tauri::command]
+pub fn try_register_shortcut(
+    app: AppHandle,
+    state: tauri::State<AppState>,
+    shortcut: String,
+) -> Result<(), ShortcutError> {
+    rebind_panel_shortcut(&app, &state, &shortcut, true)
+}
+
+// The one path set_shortcut and try_register_shortcut rebind the panel
+// through. With `persist` the combo is also saved for later launches.
+fn rebind_panel_shortcut(
+    app: &AppHandle,
+    state: &AppState,
+    shortcut: &str,
+    persist: bool,
+) -> Result<(), ShortcutError> {
+    let parsed = parse_shortcut(shortcut)?;
+    match state.shortcuts.action_for(&parsed) {
+        // Already the panel's; registering it again would fail
+        Some(ShortcutAction::TogglePanel) => {}
+        Some(action) => return Err(ShortcutError::InUse(action)),
+        None => state
+            .shortcuts
+            .register(app, ShortcutAction::TogglePanel, parsed)
+            .map_err(ShortcutError::Conflict)?,
+    }
+
+    if persist {
+        write_atomic(&state.config.get_shortcut_path(), shortcut)
+            .map_err(|e| ShortcutError::NotSaved(e.to_string()))?;
+    }
+    Ok(())
+}
+
+// Whether the app currently holds `shortcut`. Combos held by other apps
+// can't be seen, so those read as false.
+#[tauri::command]
+pub fn is_shortcut_registered(app: AppHandle, shortcut: String) -> Result<bool, ShortcutError> {
+    let parsed = parse_shortcut(&shortcut)?;
+    Ok(app.global_shortcut().is_registered(parsed))
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ShortcutBindingError {
+    action: ShortcutAction,
+    shortcut: String,
+    reason: String,
+}
+
+// The parts of the global shortcut plugin a binding transaction needs, so the
+// all-or-nothing logic doesn't depend on the OS
+trait Registrar {
+    fn register(&self, shortcut: Shortcut) -> Result<(), String>;
+    fn unregister(&self, shortcut: Shortcut);
+}
+
+impl<R: Runtime> Registrar for GlobalShortcut<R> {
+    fn register(&self, shortcut: Shortcut) -> Result<(), String> {
+        GlobalShortcut::register(self, shortcut).map_err(|e| e.to_string())
+    }
+
+    fn unregister(&self, shortcut: Shortcut) {
+        let _ = GlobalShortcut::unregister(self, shortcut);
+    }
+}
+
+// Registers a set of bindings as a single transaction. Either every binding
+// takes effect, or the shortcuts registered before the call are put back and
+// the first binding that failed is reported.
+#[tauri::command]
+pub fn register_shortcuts(
+    app: AppHandle,
+    state: tauri::State<AppState>,
+    bindings: Vec<(ShortcutAction, String)>,
+) -> Result<(), ShortcutBindingError> {
+    let mut current = state.shortcuts.bindings.lock().unwrap();
+    register_all(app.global_shortcut(), &mut current, &bindings)?;
+
+    let main_window = bindings
+        .iter()
+        .find(|(action, _)| *action == ShortcutAction::OpenMainWindow);
+    if let Some((_, shortcut)) = main_window {
+        state.tray_menu.update(&app, |tray_state| {
+            tray_state.main_window_shortcut_hint = Some(shortcut.clone())
+        });
+    }
+    Ok(())
+}
+
+fn register_all(
+    registrar: &impl Registrar,
+    current: &mut HashMap<ShortcutAction, Shortcut>,
+    bindings: &[(ShortcutAction, String)],
+) -> Result<(), ShortcutBindingError> {
+    // Validate everything up front so bad input never touches registration
+    let mut parsed: Vec<(ShortcutAction, Shortcut)> = Vec::with_capacity(bindings.len());
+    for (action, value) in bindings {
+        let shortcut = Shortcut::from_str(value).map_err(|e| ShortcutBindingError {
+            action: *action,
+            shortcut: value.clone(),
+            reason: e.to_string(),
+        })?;
+        if let Some((other, _)) = parsed.iter().find(|(_, s)| *s == shortcut) {
+            return Err(ShortcutBindingError {
+                action: *action,
+                shortcut: value.clone(),
+                reason: format!("already bound to {other:?} in this batch"),
+            });
+        }
+        if parsed.iter().any(|(a, _)| a == action) {
+            return Err(ShortcutBindingError {
+                action: *action,
+                shortcut: value.clone(),
+                reason: "action bound more than once in this batch".to_string(),
+            });
+        }
+        parsed.push((*action, shortcut));
+    }
+
+    let prior = current.clone();
+
+    // Free the shortcuts being replaced first so actions can swap combos
+    for (action, _) in &parsed {
+        if let Some(old) = prior.get(action) {
+            registrar.unregister(*old);
+        }
+    }
+
+    let mut registered = Vec::new();
+    for (index, (action, shortcut)) in parsed.iter().enumerate() {
+        let taken = prior
+            .iter()
+            .any(|(a, s)| s == shortcut && !parsed.iter().any(|(p, _)| p == a));
+        let result = if taken {
+            Err("already bound to another action".to_string())
+        } else {
+            registrar.register(*shortcut)
+        };
+
+        if let Err(reason) = result {
+            for shortcut in registered {
+                registrar.unregister(shortcut);
+            }
+            for (action, _) in &parsed {
+                if let Some(old) = prior.get(action) {
+                    if let Err(e) = registrar.register(*old) {
+                        log_error!("Failed to restore shortcut for {action:?}: {e}");
+                    }
+                }
+            }
+            return Err(ShortcutBindingError {
+                action: *action,
+                shortcut: bindings[index].1.clone(),
+                reason,
+            });
+        }
+        registered.push(*shortcut);
+    }
+
+    current.extend(parsed);
+    Ok(())
+}
+
+// Combos macOS itself uses out of the box
+const SYSTEM_SHORTCUTS: &[(&str, &str)] = &[
+    ("super+space", "Spotlight"),
+    ("super+alt+space", "Finder search"),
+    ("ctrl+space", "Input source switching"),
+    ("ctrl+alt+space", "Input source switching"),
+];
+
+// Launchers known to claim a combo by default: (app, process name, shortcut)
+const KNOWN_OWNERS: &[(&str, &str, &str)] = &[
+    ("Raycast", "Raycast", "alt+space"),
+    ("Alfred", "Alfred", "alt+space"),
+    ("ChatGPT", "ChatGPT", "alt+space"),
+    ("LaunchBar", "LaunchBar", "super+space"),
+];
+
+#[derive(serde::Serialize)]
+#[serde(tag = "owner", rename_all = "snake_case")]
+pub enum ShortcutOwner {
+    // Already bound to one of our own actions
+    Ours { action: ShortcutAction },
+    Likely { app: String, reason: String },
+    Unknown,
+}
+
+fn is_running(process_name: &str) -> bool {
+    Command::new("/usr/bin/pgrep")
+        .args(["-x", process_name])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+fn matches(shortcut: &Shortcut, value: &str) -> bool {
+    Shortcut::from_str(value).is_ok_and(|known| known == *shortcut)
+}
+
+// Best-effort guess at who holds a combo when registering it fails. There's no
+// API to ask the OS, so this only checks the system defaults and running apps
+// known to use the combo.
+#[tauri::command]
+pub fn diagnose_shortcut_owner(
+    state: tauri::State<AppState>,
+    shortcut: String,
+) -> Result<ShortcutOwner, String> {
+    let parsed = Shortcut::from_str(&shortcut).map_err(|e| e.to_string())?;
+
+    if let Some(action) = state.shortcuts.action_for(&parsed) {
+        return Ok(ShortcutOwner::Ours { action });
+    }
+
+    if let Some((_, feature)) = SYSTEM_SHORTCUTS
+        .iter()
+        .find(|(value, _)| matches(&parsed, value))
+    {
+        return Ok(ShortcutOwner::Likely {
+            app: "macOS".to_string(),
+            reason: format!("{feature} uses {shortcut} by default"),
+        });
+    }
+
+    let owner = KNOWN_OWNERS
+        .iter()
+        .filter(|(_, _, value)| matches(&parsed, value))
+        .find(|(_, process_name, _)| is_running(process_name));
+    if let Some((app, _, _)) = owner {
+        return Ok(ShortcutOwner::Likely {
+            app: app.to_string(),
+            reason: format!("{app} is running and uses {shortcut} by default"),
+        });
+    }
+
+    Ok(ShortcutOwner::Unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    // Tracks what's registered, failing the nth call to register (from 1)
+    #[derive(Default)]
+    struct FakeRegistrar {
+        registered: RefCell<Vec<Shortcut>>,
+        calls: RefCell<usize>,
+        fail_on_call: Option<usize>,
+    }
+
+    impl Registrar for FakeRegistrar {
+        fn register(&self, shortcut: Shortcut) -> Result<(), String> {
+            *self.calls.borrow_mut() += 1;
+            if Some(*self.calls.borrow()) == self.fail_on_call {
+                return Err("taken by another app".to_string());
+            }
+            self.registered.borrow_mut().push(shortcut);
+            Ok(())
+        }
+
+        fn unregister(&self, shortcut: Shortcut) {
+            self.registered.borrow_mut().retain(|s| *s != shortcut);
+        }
+    }
+
+    fn shortcut(value: &str) -> Shortcut {
+        Shortcut::from_str(value).unwrap()
+    }
+
+    fn batch(bindings: &[(ShortcutAction, &str)]) -> Vec<(ShortcutAction, String)> {
+        bindings
+            .iter()
+            .map(|(action, value)| (*action, value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn registers_every_binding() {
+        let registrar = FakeRegistrar::default();
+        let mut current = HashMap::new();
+        let bindings = batch(&[
+            (ShortcutAction::TogglePanel, "Alt+Space"),
+            (ShortcutAction::OpenMainWindow, "Alt+Shift+T"),
+        ]);
+
+        register_all(&registrar, &mut current, &bindings).unwrap();
+
+        assert_eq!(
+            *registrar.registered.borrow(),
+            vec![shortcut("Alt+Space"), shortcut("Alt+Shift+T")]
+        );
+        assert_eq!(current.len(), 2);
+    }
+
+    #[test]
+    fn failure_unregisters_the_earlier_bindings() {
+        for failing in 1..=3 {
+            let registrar = FakeRegistrar {
+                fail_on_call: Some(failing),
+                ..FakeRegistrar::default()
+            };
+            let mut current = HashMap::new();
+            let bindings = batch(&[
+                (ShortcutAction::TogglePanel, "Alt+Space"),
+                (ShortcutAction::OpenMainWindow, "Alt+Shift+T"),
+                (ShortcutAction::ToggleRecordMode, "Alt+Shift+R"),
+            ]);
+
+            let error = register_all(&registrar, &mut current, &bindings).unwrap_err();
+
+            assert_eq!(error.action, bindings[failing - 1].0);
+            assert!(registrar.registered.borrow().is_empty());
+            assert!(current.is_empty());
+        }
+    }
+
+    #[test]
+    fn failure_restores_the_previous_shortcuts() {
+        let registrar = FakeRegistrar::default();
+        let mut current = HashMap::new();
+        let initial = batch(&[
+            (ShortcutAction::TogglePanel, "Alt+Space"),
+            (ShortcutAction::OpenMainWindow, "Alt+Shift+T"),
+        ]);
+        register_all(&registrar, &mut current, &initial).unwrap();
+        let before = current.clone();
+
+        // The two initial registrations were calls 1 and 2, so this fails
+        // the second binding of the new batch
+        let registrar = FakeRegistrar {
+            registered: registrar.registered,
+            calls: RefCell::new(2),
+            fail_on_call: Some(4),
+        };
+        let rebind = batch(&[
+            (ShortcutAction::TogglePanel, "Ctrl+Space"),
+            (ShortcutAction::OpenMainWindow, "Ctrl+Shift+T"),
+        ]);
+        let error = register_all(&registrar, &mut current, &rebind).unwrap_err();
+
+        assert_eq!(error.action, ShortcutAction::OpenMainWindow);
+        let mut registered = registrar.registered.borrow().clone();
+        registered.sort_by_key(|s| s.id());
+        let mut expected = vec![shortcut("Alt+Space"), shortcut("Alt+Shift+T")];
+        expected.sort_by_key(|s| s.id());
+        assert_eq!(registered, expected);
+        assert_eq!(current, before);
+    }
+
+    #[test]
+    fn invalid_input_registers_nothing() {
+        let registrar = FakeRegistrar::default();
+        let mut current = HashMap::new();
+        let bindings = batch(&[
+            (ShortcutAction::TogglePanel, "Alt+Space"),
+            (ShortcutAction::OpenMainWindow, "Alt+NotAKey"),
+        ]);
+
+        let error = register_all(&registrar, &mut current, &bindings).unwrap_err();
+
+        assert_eq!(error.action, ShortcutAction::OpenMainWindow);
+        assert_eq!(*registrar.calls.borrow(), 0);
+    }
+}
@@ -0,0 +1,163 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::AppState;
+
+const LOGS_DIR: &str = "logs";
+const SCREENSHOTS_DIR: &str = "screenshots";
+const RECORDINGS_DIR: &str = "recordings";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Category {
+    Logs,
+    Screenshots,
+    Recordings,
+    Captures,
+    // Config, PID files, server builds and anything else we don't own as an
+    // artifact. Counted, but never cleaned up.
+    Other,
+}
+
+fn classify(relative: &Path) -> Category {
+    let top = relative
+        .components()
+        .next()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let is_file_at_root = relative.components().count() == 1;
+
+    match top.as_str() {
+        LOGS_DIR => Category::Logs,
+        SCREENSHOTS_DIR => Category::Screenshots,
+        RECORDINGS_DIR => Category::Recordings,
+        name if is_file_at_root && name.ends_with(".log") => Category::Logs,
+        name if is_file_at_root && name.starts_with("captures") && name.ends_with(".jsonl") => {
+            Category::Captures
+        }
+        _ => Category::Other,
+    }
+}
+
+struct Entry {
+    path: PathBuf,
+    category: Category,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+// Every regular file under the config dir. Symlinks aren't followed, so
+// nothing outside the dir is ever counted or deleted.
+fn walk(root: &Path) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        for item in fs::read_dir(&dir)?.flatten() {
+            let path = item.path();
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                dirs.push(path);
+            } else if metadata.is_file() {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                entries.push(Entry {
+                    category: classify(relative),
+                    size: metadata.len(),
+                    modified: metadata.modified().ok(),
+                    path,
+                });
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+#[derive(Default, serde::Serialize)]
+pub struct StorageUsage {
+    logs: u64,
+    screenshots: u64,
+    recordings: u64,
+    captures: u64,
+    other: u64,
+    total: u64,
+}
+
+// Sizes are in bytes
+#[tauri::command]
+pub fn get_storage_usage(state: tauri::State<AppState>) -> Result<StorageUsage, String> {
+    let entries = walk(state.config.get_config_dir()).map_err(|e| e.to_string())?;
+
+    let mut usage = StorageUsage::default();
+    for entry in entries {
+        let bucket = match entry.category {
+            Category::Logs => &mut usage.logs,
+            Category::Screenshots => &mut usage.screenshots,
+            Category::Recordings => &mut usage.recordings,
+            Category::Captures => &mut usage.captures,
+            Category::Other => &mut usage.other,
+        };
+        *bucket += entry.size;
+        usage.total += entry.size;
+    }
+
+    Ok(usage)
+}
+
+#[derive(serde::Serialize)]
+pub struct CleanupReport {
+    dry_run: bool,
+    files: Vec<PathBuf>,
+    bytes: u64,
+}
+
+// Deletes logs, screenshots, recordings and capture logs last modified more
+// than `older_than_days` ago. With `dry_run` the same report is returned but
+// nothing is removed.
+#[tauri::command]
+pub fn cleanup_old_artifacts(
+    state: tauri::State<AppState>,
+    older_than_days: u64,
+    dry_run: Option<bool>,
+) -> Result<CleanupReport, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    let cutoff = SystemTime::now()
+        .checked_sub(Duration::from_secs(older_than_days * 24 * 60 * 60))
+        .ok_or("Retention period is too long")?;
+
+    let entries = walk(state.config.get_config_dir()).map_err(|e| e.to_string())?;
+
+    let mut report = CleanupReport {
+        dry_run,
+        files: Vec::new(),
+        bytes: 0,
+    };
+    for entry in entries {
+        if entry.category == Category::Other {
+            continue;
+        }
+        match entry.modified {
+            Some(modified) if modified < cutoff => {}
+            _ => continue,
+        }
+        if !dry_run {
+            if let Err(e) = fs::remove_file(&entry.path) {
+                log_error!("Failed to remove {}: {e}", entry.path.display());
+                continue;
+            }
+        }
+        report.bytes += entry.size;
+        report.files.push(entry.path);
+    }
+
+    tracing::info!(
+        dry_run,
+        files = report.files.len(),
+        bytes = report.bytes,
+        "cleaned up old artifacts"
+    );
+    Ok(report)
+}
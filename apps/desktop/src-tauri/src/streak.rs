@@ -0,0 +1,90 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{Local, NaiveDate};
+use tauri::{AppHandle, Listener, Manager};
+
+use crate::config::write_atomic;
+use crate::AppState;
+
+#[derive(Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct StreakData {
+    current_streak: u32,
+    longest_streak: u32,
+    // Local calendar day of the most recent capture
+    last_capture_date: Option<NaiveDate>,
+}
+
+impl StreakData {
+    fn record(&mut self, today: NaiveDate) {
+        match self.last_capture_date {
+            Some(last) if last == today => return,
+            Some(last) if last.succ_opt() == Some(today) => self.current_streak += 1,
+            _ => self.current_streak = 1,
+        }
+        self.longest_streak = self.longest_streak.max(self.current_streak);
+        self.last_capture_date = Some(today);
+    }
+
+    // The stored streak only changes on capture, so a missed day is applied
+    // when it's read
+    fn as_of(&self, today: NaiveDate) -> StreakData {
+        let mut data = self.clone();
+        let alive = data
+            .last_capture_date
+            .is_some_and(|last| last == today || last.succ_opt() == Some(today));
+        if !alive {
+            data.current_streak = 0;
+        }
+        data
+    }
+}
+
+// Days in a row with at least one capture, persisted in the config dir
+pub struct CaptureStreak {
+    path: PathBuf,
+    data: Mutex<StreakData>,
+}
+
+impl CaptureStreak {
+    pub fn load(path: PathBuf) -> Self {
+        let data = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        CaptureStreak {
+            path,
+            data: Mutex::new(data),
+        }
+    }
+
+    fn record_capture(&self) -> io::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.record(Local::now().date_naive());
+        write_atomic(&self.path, &serde_json::to_string(&*data)?)
+    }
+}
+
+// The panel emits `thought-captured` after every successful save
+pub fn listen_for_captures(app: &AppHandle) {
+    let handle = app.clone();
+    app.listen_any("thought-captured", move |_| {
+        if let Some(state) = handle.try_state::<AppState>() {
+            if let Err(e) = state.capture_streak.record_capture() {
+                log_error!("Failed to update capture streak: {e}");
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_capture_streak(state: tauri::State<AppState>) -> StreakData {
+    state
+        .capture_streak
+        .data
+        .lock()
+        .unwrap()
+        .as_of(Local::now().date_naive())
+}
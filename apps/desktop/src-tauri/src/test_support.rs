@@ -0,0 +1,56 @@
+// Helpers shared by the unit tests
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::process::{Child, Command};
+#[cfg(unix)]
+use std::thread;
+#[cfg(unix)]
+use std::time::Duration;
+
+// A fresh directory under the system temp dir, removed when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("thoughts-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// A port nothing is listening on, so reconcile doesn't report it in use
+pub fn unused_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+// A copy of `sleep` at `path`, started so there's a process running it
+#[cfg(unix)]
+pub fn spawn_copy_of_sleep(path: &Path) -> Child {
+    let sleep = PathBuf::from("/bin/sleep").canonicalize().unwrap();
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::copy(sleep, path).unwrap();
+    // Another test forking while the copy was open for writing can make exec
+    // fail with ETXTBSY for a moment
+    for _ in 0..20 {
+        match Command::new(path).arg("30").spawn() {
+            Err(e) if e.raw_os_error() == Some(libc::ETXTBSY) => {
+                thread::sleep(Duration::from_millis(50))
+            }
+            result => return result.unwrap(),
+        }
+    }
+    panic!("{} stayed busy", path.display());
+}
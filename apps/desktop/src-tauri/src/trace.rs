@@ -0,0 +1,127 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{Local, NaiveDate};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{prelude::*, reload, Registry};
+
+use crate::config::Config;
+use crate::AppState;
+
+// Lets set_trace_level swap the filter without rebuilding the subscriber
+static LEVEL_HANDLE: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+
+// The log starts over each day, or sooner once it reaches this size
+const MAX_LOG_BYTES: u64 = 10 * 1024 * 1024;
+
+// Rotated logs kept next to the current one, as trace.log.1 (newest) onwards
+const ROTATED_LOGS_KEPT: u32 = 5;
+
+// Append-only log file that rotates itself, so the log stays bounded in
+// packaged builds where nobody is watching it
+struct RotatingLog {
+    path: PathBuf,
+    file: File,
+    opened_on: NaiveDate,
+    size: u64,
+}
+
+impl RotatingLog {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        // A log left from an earlier day rotates on the first write
+        let opened_on = metadata
+            .modified()
+            .map(|modified| chrono::DateTime::<Local>::from(modified).date_naive())
+            .unwrap_or_else(|_| Local::now().date_naive());
+        Ok(RotatingLog {
+            path: path.to_path_buf(),
+            file,
+            opened_on,
+            size: metadata.len(),
+        })
+    }
+
+    fn rotated_path(&self, index: u32) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let _ = fs::remove_file(self.rotated_path(ROTATED_LOGS_KEPT));
+        for index in (1..ROTATED_LOGS_KEPT).rev() {
+            let _ = fs::rename(self.rotated_path(index), self.rotated_path(index + 1));
+        }
+        fs::rename(&self.path, self.rotated_path(1))?;
+        *self = RotatingLog::open(&self.path)?;
+        Ok(())
+    }
+}
+
+impl Write for RotatingLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let new_day = Local::now().date_naive() != self.opened_on;
+        if self.size > 0 && (new_day || self.size + buf.len() as u64 > MAX_LOG_BYTES) {
+            // Keep logging to the old file rather than lose the event
+            if let Err(e) = self.rotate() {
+                eprintln!("Failed to rotate {}: {e}", self.path.display());
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// Spans and events go to the trace log as JSON lines, one object per event
+// with its span stack, so a capture can be followed from the shortcut through
+// context gathering to the server.
+pub fn init(config: &Config) -> io::Result<()> {
+    let path = config.get_trace_log_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = RotatingLog::open(&path)?;
+
+    let (filter, handle) = reload::Layer::new(config.get_trace_level());
+    let output = tracing_subscriber::fmt::layer()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_writer(Mutex::new(file));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(output)
+        .try_init()
+        .map_err(io::Error::other)?;
+    let _ = LEVEL_HANDLE.set(handle);
+    Ok(())
+}
+
+// Where the current log is, for attaching to bug reports. Older ones sit
+// next to it as trace.log.1 onwards.
+#[tauri::command]
+pub fn get_log_path(state: tauri::State<AppState>) -> String {
+    state.config.get_trace_log_path().display().to_string()
+}
+
+// One of off, error, warn, info, debug or trace
+#[tauri::command]
+pub fn set_trace_level(level: String) -> Result<(), String> {
+    let level: LevelFilter = level
+        .parse()
+        .map_err(|_| format!("Unknown trace level: {level}"))?;
+    let handle = LEVEL_HANDLE.get().ok_or("Tracing isn't initialized")?;
+    handle
+        .modify(|filter| *filter = level)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,159 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{
+    image::Image,
+    menu::{CheckMenuItem, Menu, MenuItem},
+    AppHandle, Manager, Runtime,
+};
+
+use crate::AppState;
+
+pub const TRAY_ID: &str = "tray";
+
+// Template images, so macOS tints them to match the menu bar
+const ICON: &[u8] = include_bytes!("../icons/32x32.png");
+const RECORDING_ICON: &[u8] = include_bytes!("../icons/32x32-recording.png");
+
+// Rapid state changes within this window are coalesced into one rebuild
+const REBUILD_DEBOUNCE: Duration = Duration::from_millis(150);
+
+// Everything the tray menu renders from. Menu changes go through here rather
+// than poking at menu items directly, so a rebuild always reflects the latest
+// state.
+#[derive(Clone, Default)]
+pub struct TrayMenuState {
+    pub record_mode: bool,
+    // Shown next to Open; None while the main window shortcut isn't registered
+    pub main_window_shortcut_hint: Option<String>,
+    // Thoughts captured today as reported by the frontend, hidden when 0
+    pub badge: u32,
+}
+
+pub struct TrayMenu {
+    state: Mutex<TrayMenuState>,
+    generation: AtomicU64,
+}
+
+impl TrayMenu {
+    pub fn new(main_window_shortcut_hint: Option<&str>) -> Self {
+        TrayMenu {
+            state: Mutex::new(TrayMenuState {
+                main_window_shortcut_hint: main_window_shortcut_hint.map(str::to_owned),
+                ..TrayMenuState::default()
+            }),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    pub fn build<R: Runtime, M: Manager<R>>(&self, manager: &M) -> tauri::Result<Menu<R>> {
+        let state = self.state.lock().unwrap().clone();
+        build_menu(manager, &state)
+    }
+
+    pub fn icon(&self) -> tauri::Result<Image<'static>> {
+        let bytes = if self.state.lock().unwrap().record_mode {
+            RECORDING_ICON
+        } else {
+            ICON
+        };
+        Image::from_bytes(bytes)
+    }
+
+    pub fn tooltip(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut tooltip = "Thoughts".to_string();
+        if state.record_mode {
+            tooltip.push_str(" — Recording");
+        }
+        if state.badge > 0 {
+            tooltip.push_str(&format!(" — {} today", state.badge));
+        }
+        tooltip
+    }
+
+    // Text next to the icon in the menu bar; None removes it
+    pub fn title(&self) -> Option<String> {
+        match self.state.lock().unwrap().badge {
+            0 => None,
+            count => Some(format!("✎ {count}")),
+        }
+    }
+
+    pub fn update(&self, app: &AppHandle, apply: impl FnOnce(&mut TrayMenuState)) {
+        apply(&mut self.state.lock().unwrap());
+        self.schedule_rebuild(app);
+    }
+
+    fn schedule_rebuild(&self, app: &AppHandle) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let app = app.clone();
+
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(REBUILD_DEBOUNCE).await;
+
+            let Some(state) = app.try_state::<AppState>() else {
+                return;
+            };
+            // A newer change was scheduled while we slept; let it do the rebuild
+            if state.tray_menu.generation.load(Ordering::SeqCst) != generation {
+                return;
+            }
+
+            let handle = app.clone();
+            let _ = app.run_on_main_thread(move || {
+                let state = handle.state::<AppState>();
+                let Some(tray) = handle.tray_by_id(TRAY_ID) else {
+                    return;
+                };
+                match state.tray_menu.build(&handle) {
+                    Ok(menu) => {
+                        let _ = tray.set_menu(Some(menu));
+                    }
+                    Err(e) => log_error!("Failed to rebuild tray menu: {e}"),
+                }
+                match state.tray_menu.icon() {
+                    Ok(icon) => {
+                        let _ = tray.set_icon(Some(icon));
+                        let _ = tray.set_icon_as_template(true);
+                    }
+                    Err(e) => log_error!("Failed to load tray icon: {e}"),
+                }
+                let _ = tray.set_tooltip(Some(state.tray_menu.tooltip()));
+                let _ = tray.set_title(state.tray_menu.title());
+            });
+        });
+    }
+}
+
+// Shows `count` next to the tray icon, e.g. today's captures. 0 clears it.
+#[tauri::command]
+pub fn set_tray_badge(app: AppHandle, count: u32) {
+    app.state::<AppState>()
+        .tray_menu
+        .update(&app, |tray_state| tray_state.badge = count);
+}
+
+fn build_menu<R: Runtime, M: Manager<R>>(
+    manager: &M,
+    state: &TrayMenuState,
+) -> tauri::Result<Menu<R>> {
+    let open_i = MenuItem::with_id(
+        manager,
+        "open",
+        "Open",
+        true,
+        state.main_window_shortcut_hint.as_deref(),
+    )?;
+    let record_mode_i = CheckMenuItem::with_id(
+        manager,
+        "record_mode",
+        "Record Mode",
+        true,
+        state.record_mode,
+        None::<&str>,
+    )?;
+    let quit_i = MenuItem::with_id(manager, "quit", "Quit", true, None::<&str>)?;
+
+    Menu::with_items(manager, &[&record_mode_i, &open_i, &quit_i])
+}
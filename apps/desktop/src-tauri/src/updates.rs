@@ -0,0 +1,95 @@
+use std::process::Command;
+
+use semver::Version;
+use tauri::AppHandle;
+
+use crate::AppState;
+
+// Gives up on the manifest after this long so a dead network can't hang the
+// check
+const MANIFEST_TIMEOUT_SECS: u32 = 10;
+
+// Why an update check failed. Crosses to the frontend tagged the same way as
+// context errors, e.g. `{ "kind": "network", "detail": "..." }`.
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum UpdateError {
+    // THOUGHTS_UPDATE_MANIFEST_URL isn't set
+    NotConfigured,
+    Network(String),
+    InvalidManifest(String),
+}
+
+impl std::fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateError::NotConfigured => write!(f, "No update manifest URL is configured"),
+            UpdateError::Network(message) => write!(f, "Couldn't fetch the manifest: {message}"),
+            UpdateError::InvalidManifest(message) => write!(f, "Invalid manifest: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for UpdateError {}
+
+// The manifest is a JSON object with at least `{ "version": "1.2.3" }`
+#[derive(serde::Deserialize)]
+struct Manifest {
+    version: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct UpdateInfo {
+    current: String,
+    latest: String,
+    update_available: bool,
+}
+
+#[tauri::command]
+pub fn get_app_version(app: AppHandle) -> String {
+    app.package_info().version.to_string()
+}
+
+// curl ships with macOS, which saves pulling in an HTTP client for one request
+fn fetch_manifest(url: &str) -> Result<Manifest, UpdateError> {
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(["--max-time", &MANIFEST_TIMEOUT_SECS.to_string()])
+        .arg(url)
+        .output()
+        .map_err(|e| UpdateError::Network(e.to_string()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(UpdateError::Network(stderr.trim().to_string()));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| UpdateError::InvalidManifest(e.to_string()))
+}
+
+// Compares the running version against the manifest at
+// THOUGHTS_UPDATE_MANIFEST_URL. Only reports; nothing is downloaded.
+#[tauri::command]
+pub async fn check_for_update(app: AppHandle) -> Result<UpdateInfo, UpdateError> {
+    use tauri::Manager;
+
+    let Some(url) = app
+        .state::<AppState>()
+        .config
+        .get_update_manifest_url()
+        .map(str::to_owned)
+    else {
+        return Err(UpdateError::NotConfigured);
+    };
+    let current = app.package_info().version.clone();
+
+    let manifest = tauri::async_runtime::spawn_blocking(move || fetch_manifest(&url))
+        .await
+        .map_err(|e| UpdateError::Network(e.to_string()))??;
+    let latest = Version::parse(manifest.version.trim_start_matches('v'))
+        .map_err(|e| UpdateError::InvalidManifest(format!("{}: {e}", manifest.version)))?;
+
+    Ok(UpdateInfo {
+        current: current.to_string(),
+        update_available: latest > current,
+        latest: latest.to_string(),
+    })
+}
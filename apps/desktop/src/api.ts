@@ -9,7 +9,7 @@ let cachedClient: ReturnType<typeof trpc.createClient> | null = null
 
 async function getSidecarPort(): Promise<number> {
   if (cachedPort === null) {
-    cachedPort = await invoke<number>("get_sidecar_port")
+    cachedPort = await invoke<number>("get_server_port")
   }
   return cachedPort
 }
@@ -2,6 +2,7 @@ import { trpc } from "../api"
 import { useEffect, useState, useMemo, useRef } from "react"
 import { formatInTimeZone } from "date-fns-tz"
 import { invoke } from "@tauri-apps/api/core"
+import { listen } from "@tauri-apps/api/event"
 import "./scrollbar.css"
 import type { ContextInfo, Image, LocationInfo } from "./quick-panel"
 
@@ -96,6 +97,17 @@ function highlightMatches(text: string, searchQuery: string): React.ReactNode {
 export function MainWindow() {
   const [searchQuery, setSearchQuery] = useState("")
   const debouncedSearchQuery = useDebounce(searchQuery, 300)
+  const [privacyBlur, setPrivacyBlur] = useState(false)
+
+  useEffect(() => {
+    invoke<boolean>("get_privacy_blur").then(setPrivacyBlur)
+    const unlisten = listen<boolean>("privacy-blur", ({ payload }) =>
+      setPrivacyBlur(payload)
+    )
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [])
 
   const handleReplayClick = async (thoughtId: number) => {
     try {
@@ -125,6 +137,15 @@ export function MainWindow() {
     }
   )
 
+  // The window is kept when reopened from the tray, so pick up what was
+  // captured in the meantime
+  useEffect(() => {
+    const unlisten = listen("main-window-focused", () => refetch())
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [refetch])
+
   const filteredThoughts = useMemo(
     () => data?.pages.flatMap((page) => page.items) ?? [],
     [data]
@@ -152,6 +173,12 @@ export function MainWindow() {
 
   return (
     <div className="flex flex-col h-screen w-screen bg-zinc-900 text-white select-none">
+      {privacyBlur && (
+        <div
+          className="fixed inset-0 z-50 backdrop-blur-xl bg-zinc-900/60"
+          onClick={() => invoke("set_privacy_blur", { enabled: false })}
+        />
+      )}
       <div
         className="flex flex-row border-b border-zinc-800 px-2 min-h-[28px] py-2"
         data-tauri-drag-region
@@ -1,5 +1,6 @@
 import { useState, useEffect, useRef } from "react"
 import { invoke } from "@tauri-apps/api/core"
+import { emit, listen } from "@tauri-apps/api/event"
 import { trpc } from "../api"
 import { getCurrentWindow } from "@tauri-apps/api/window"
 import { cn } from "../lib/utils"
@@ -7,11 +8,21 @@ import { cn } from "../lib/utils"
 export interface SpotifyTrackInfo {
   artist: string
   track: string
+  is_playing: boolean
+  position_seconds: number | null
+  duration_seconds: number | null
+}
+
+export interface BrowserUrl {
+  browser: string
+  url: string
 }
 
 export interface FocusedAppInfo {
   name: string
   bundleId: string
+  pid: number
+  windowTitle: string | null
 }
 
 export interface LocationInfo {
@@ -37,6 +48,8 @@ export interface LocationInfo {
   postalCode?: string
   v_accuracy: string
   country?: string
+  accuracy_meters: number | null
+  stale: boolean
 }
 
 export interface Image {
@@ -46,9 +59,11 @@ export interface Image {
 
 export interface ContextInfo {
   url?: string
+  browser?: string
   spotify?: SpotifyTrackInfo
   focusedApp?: FocusedAppInfo
   location?: LocationInfo
+  terminalCommand?: string
   images?: Image[]
 }
 
@@ -107,6 +122,7 @@ export function QuickPanel() {
     { mimeType: string; dataUri: string }[]
   >([])
   const inputRef = useRef<HTMLTextAreaElement>(null)
+  const containerRef = useRef<HTMLDivElement>(null)
 
   // Record mode state
   const [recordMode, setRecordMode] = useState(false)
@@ -116,6 +132,9 @@ export function QuickPanel() {
   const [sequenceNum, setSequenceNum] = useState(0)
   const lastInputValueRef = useRef("") // Track exact previous value for diffing
 
+  const [toast, setToast] = useState<string | null>(null)
+  const [prompt, setPrompt] = useState<string | null>(null)
+
   const { mutate: createThought } = trpc.createThought.useMutation()
   const { mutate: createEditOperation } = trpc.createEditOperation.useMutation()
   const { mutate: updateHistoryThoughtId } =
@@ -123,20 +142,42 @@ export function QuickPanel() {
   const { mutate: deleteHistory } = trpc.deleteEditOperations.useMutation()
 
   const fetchContextInfo = async () => {
-    const [url, spotifyInfo, focusedAppInfo, locationInfo] =
+    const focusedApp = await invoke<FocusedAppInfo>("get_focused_app").catch(
+      () => undefined
+    )
+    // Only gather what's configured as relevant for the frontmost app
+    const sources = await invoke<string[]>("get_context_sources_for_app", {
+      bundleId: focusedApp?.bundleId ?? "",
+    }).catch(() => [])
+    const gather = <T,>(source: string, command: string) =>
+      sources.includes(source)
+        ? invoke<T>(command)
+        : Promise.reject(new Error(`${source} not gathered`))
+
+    const [url, spotifyInfo, locationInfo, terminalCommand] =
       await Promise.allSettled([
-        invoke<string>("active_arc_url"),
-        invoke<SpotifyTrackInfo>("get_spotify_track"),
-        invoke<FocusedAppInfo>("get_focused_app"),
-        invoke<LocationInfo>("get_location"),
+        gather<BrowserUrl | null>("browser_url", "active_browser_url"),
+        gather<SpotifyTrackInfo>("spotify", "get_spotify_track"),
+        gather<LocationInfo>("location", "get_location"),
+        gather<string | null>("terminal_command", "get_last_terminal_command"),
       ])
 
-    setContextInfo({
-      url: url.status === "fulfilled" ? url.value : undefined,
+    const context: ContextInfo = {
+      url: url.status === "fulfilled" ? url.value?.url : undefined,
+      browser: url.status === "fulfilled" ? url.value?.browser : undefined,
       spotify: spotifyInfo.status === "fulfilled" ? spotifyInfo.value : undefined,
-      focusedApp: focusedAppInfo.status === "fulfilled" ? focusedAppInfo.value : undefined,
+      focusedApp,
       location: locationInfo.status === "fulfilled" ? locationInfo.value : undefined,
-    })
+      terminalCommand:
+        terminalCommand.status === "fulfilled"
+          ? (terminalCommand.value ?? undefined)
+          : undefined,
+    }
+    setContextInfo(context)
+    // Logged for get_context_at while record mode is on, ignored otherwise
+    invoke("record_context", { context }).catch((err) =>
+      console.error("Failed to record context:", err)
+    )
   }
 
   useEffect(() => {
@@ -153,11 +194,87 @@ export function QuickPanel() {
     fetchContextInfo()
     inputRef.current?.focus()
 
+    let toastTimeout: ReturnType<typeof setTimeout> | undefined
+    const showToast = (message: string, durationMs: number) => {
+      clearTimeout(toastTimeout)
+      setToast(message)
+      toastTimeout = setTimeout(() => setToast(null), durationMs)
+    }
+    const unlistenToast = listen<{ message: string; duration_ms: number }>(
+      "panel-toast",
+      ({ payload }) => showToast(payload.message, payload.duration_ms)
+    )
+    const unlistenServerRestarted = listen("server-restarted", () =>
+      showToast("Server restarted after a crash", 3000)
+    )
+    const unlistenServerRestartFailed = listen("server-restart-failed", () =>
+      showToast("Server keeps crashing, restart the app", 5000)
+    )
+
+    const unlistenRecordMode = listen<{
+      enabled: boolean
+      session_id: number | null
+    }>("record-mode-changed", ({ payload }) => {
+      if (payload.enabled) {
+        // Starting new recording session - use the fresh ID and sync baseline
+        currentSessionIdRef.current = payload.session_id ?? -Date.now()
+        lastInputValueRef.current = inputRef.current?.value ?? ""
+        setSequenceNum(0)
+        // Gives the session's log a snapshot from its start
+        fetchContextInfo()
+      } else {
+        // Reset counts when turning off (baseline stays synced for next session)
+        setEditCount(0)
+        setConfirmingDiscard(false)
+      }
+      setRecordMode(payload.enabled)
+    })
+
+    const unlistenScheduledPrompt = listen<string | null>(
+      "scheduled-prompt",
+      ({ payload }) => {
+        setPrompt(payload)
+        inputRef.current?.focus()
+      }
+    )
+
+    const unlistenScheduledCapture = listen<string>(
+      "scheduled-capture",
+      ({ payload }) => {
+        createThought(
+          { content: payload, metadata: JSON.stringify({ scheduled: true }) },
+          {
+            onSuccess: () => emit("thought-captured"),
+            onError: (err) =>
+              console.error("Failed to save scheduled capture:", err),
+          }
+        )
+      }
+    )
+
     return () => {
       unlistenVisibilityChange.then((unlisten) => unlisten())
+      unlistenToast.then((unlisten) => unlisten())
+      unlistenScheduledPrompt.then((unlisten) => unlisten())
+      unlistenScheduledCapture.then((unlisten) => unlisten())
+      unlistenServerRestarted.then((unlisten) => unlisten())
+      unlistenServerRestartFailed.then((unlisten) => unlisten())
+      unlistenRecordMode.then((unlisten) => unlisten())
+      clearTimeout(toastTimeout)
     }
   }, [])
 
+  // Lets the window follow the content when auto-resize is on
+  useEffect(() => {
+    const container = containerRef.current
+    if (!container) return
+    const observer = new ResizeObserver(() => {
+      emit("panel-content-height", container.offsetHeight)
+    })
+    observer.observe(container)
+    return () => observer.disconnect()
+  }, [])
+
   useEffect(() => {
     const textarea = inputRef.current
     if (textarea) {
@@ -211,22 +328,9 @@ export function QuickPanel() {
     if (e.metaKey && e.key.toLowerCase() === "r") {
       e.preventDefault()
       try {
-        const newState = await invoke<boolean>("toggle_record_mode")
-
-        if (newState) {
-          // Starting new recording session - generate fresh ID and sync baseline
-          currentSessionIdRef.current = -Date.now()
-          lastInputValueRef.current = input
-          setSequenceNum(0)
-        }
-
-        setRecordMode(newState)
-
-        if (!newState && editCount > 0) {
-          // Reset counts when turning off (baseline stays synced for next session)
-          setEditCount(0)
-          setConfirmingDiscard(false)
-        }
+        // State is applied by the record-mode-changed listener, which also
+        // covers toggles from the tray
+        await invoke<boolean>("toggle_record_mode", { sessionId: -Date.now() })
       } catch (err) {
         console.error("Failed to toggle record mode", err)
       }
@@ -292,14 +396,14 @@ export function QuickPanel() {
         let thoughtText = trimmedInput
 
         if (contextInfo) {
-          thoughtText += `\n\nFrom: ${contextInfo.url}`
+          thoughtText += "\n"
+          if (contextInfo.url) {
+            thoughtText += `\nFrom: ${contextInfo.url}`
+          }
           if (contextInfo.focusedApp) {
             thoughtText += `\nFocused app: ${contextInfo.focusedApp.name}`
           }
-          if (
-            contextInfo.spotify &&
-            contextInfo.spotify.artist !== "Not playing"
-          ) {
+          if (contextInfo.spotify?.is_playing) {
             thoughtText += `\nListening to: ${contextInfo.spotify.track} by ${contextInfo.spotify.artist}`
           }
           if (contextInfo.location) {
@@ -314,11 +418,19 @@ export function QuickPanel() {
           }
         }
 
+        // Tags stashed by automation via set_next_capture_metadata
+        const extraMetadata = await invoke<Record<string, unknown> | null>(
+          "take_next_capture_metadata"
+        ).catch(() => null)
+
         const metadata = {
+          ...extraMetadata,
           url: contextInfo?.url ?? null,
+          browser: contextInfo?.browser ?? null,
           spotify: contextInfo?.spotify ?? null,
           focusedApp: contextInfo?.focusedApp ?? null,
           location: contextInfo?.location ?? null,
+          terminalCommand: contextInfo?.terminalCommand ?? null,
           images: pastedImages.map((img) => ({
             mimeType: img.mimeType,
             dataUri: img.dataUri,
@@ -329,6 +441,8 @@ export function QuickPanel() {
           { content: thoughtText, metadata: JSON.stringify(metadata) },
           {
             onSuccess: (newThought) => {
+              emit("thought-captured")
+
               // Update edit history with real thought ID if recording
               if (recordMode && editCount > 0) {
                 updateHistoryThoughtId({
@@ -338,6 +452,7 @@ export function QuickPanel() {
               }
 
               setInput("")
+              setPrompt(null)
               setPastedImages([])
               setEditCount(0)
               setSequenceNum(0)
@@ -402,9 +517,15 @@ export function QuickPanel() {
   return (
     <div className="flex w-full items-start justify-center h-auto">
       <div
+        ref={containerRef}
         className="w-[600px] bg-[#1e1e1e] pt-2 pb-1 px-2 rounded-xl overflow-hidden relative"
         data-tauri-drag-region
       >
+        {toast && (
+          <div className="absolute top-2 right-2 px-2 py-1 rounded-lg bg-white/10 text-xs text-white">
+            {toast}
+          </div>
+        )}
         <textarea
           ref={inputRef}
           value={input}
@@ -412,7 +533,7 @@ export function QuickPanel() {
           onKeyDown={handleKeyDown}
           onPaste={handlePaste}
           className="w-full px-2 bg-transparent text-white text-lg outline-none placeholder:text-white/50 resize-none overflow-hidden leading-[20px]"
-          placeholder="wazzzzzup"
+          placeholder={prompt ?? "wazzzzzup"}
           rows={1}
         />
         <div className="w-full px-2">
@@ -471,8 +592,7 @@ export function QuickPanel() {
                 {contextInfo.focusedApp && (
                   <span>{`${contextInfo.url ? " • " : ""}${contextInfo.focusedApp.name}`}</span>
                 )}
-                {contextInfo.spotify &&
-                  contextInfo.spotify.artist !== "Not playing" && (
+                {contextInfo.spotify?.is_playing && (
                     <span>{` • ${contextInfo.spotify.track} by ${contextInfo.spotify.artist}`}</span>
                   )}
                 {contextInfo.location && (
@@ -97,7 +97,7 @@ The main table storing captured thoughts.
   - spotify: { track: string, artist: string } — what was playing
   - urls: string[] — active browser URLs
   - location: { latitude: number, longitude: number, address: string, city: string, state: string, country: string } — where the user was
-  - focusedApp: { name: string, bundleId: string } — what app was focused
+  - focusedApp: { name: string, bundleId: string, pid?: number, windowTitle?: string | null } — what app was focused and its front window
 - timestamp: TEXT — ISO datetime string (default: CURRENT_TIMESTAMP)
 
 ## Important: metadata context is ambient, not causal